@@ -0,0 +1,232 @@
+use super::scanner::Span;
+
+// The abstract syntax tree only keeps what is needed to run
+// the program. Every node knows the source code it came from,
+// so errors found while walking the tree can point at it.
+
+pub struct Identifier
+{
+    pub name: String,
+    pub span: Span,
+}
+
+pub enum Literal
+{
+    Number(f64),
+    // With the escapes already replaced
+    String(String),
+    Bool(bool),
+    Nil,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum UnaryOp
+{
+    Negate,
+    Not,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum BinaryOp
+{
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum LogicalOp
+{
+    And,
+    Or,
+}
+
+pub enum Expr
+{
+    Literal
+    {
+        value: Literal, span: Span
+    },
+    Grouping
+    {
+        expr: Box<Expr>, span: Span
+    },
+    Unary
+    {
+        operator: UnaryOp,
+        right: Box<Expr>,
+        span: Span,
+    },
+    Binary
+    {
+        left: Box<Expr>,
+        operator: BinaryOp,
+        right: Box<Expr>,
+        span: Span,
+    },
+    // `and` and `or` only evaluate the right operand when needed
+    Logical
+    {
+        left: Box<Expr>,
+        operator: LogicalOp,
+        right: Box<Expr>,
+        span: Span,
+    },
+    Variable
+    {
+        name: Identifier
+    },
+    Assign
+    {
+        name: Identifier,
+        value: Box<Expr>,
+        span: Span,
+    },
+    Call
+    {
+        callee: Box<Expr>,
+        arguments: Vec<Expr>,
+        span: Span,
+    },
+    Get
+    {
+        object: Box<Expr>,
+        name: Identifier,
+        span: Span,
+    },
+    Set
+    {
+        object: Box<Expr>,
+        name: Identifier,
+        value: Box<Expr>,
+        span: Span,
+    },
+    This
+    {
+        span: Span
+    },
+    Super
+    {
+        method: Identifier, span: Span
+    },
+    // Code that couldn't be parsed, only produced by the tolerant parser
+    Error
+    {
+        span: Span
+    },
+}
+
+impl Expr
+{
+    pub fn span(&self) -> Span
+    {
+        match self
+        {
+            Expr::Variable { name } => name.span,
+            Expr::Literal { span, .. }
+            | Expr::Grouping { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Binary { span, .. }
+            | Expr::Logical { span, .. }
+            | Expr::Assign { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::Get { span, .. }
+            | Expr::Set { span, .. }
+            | Expr::This { span }
+            | Expr::Super { span, .. }
+            | Expr::Error { span } => *span,
+        }
+    }
+}
+
+pub struct Function
+{
+    pub name: Identifier,
+    pub params: Vec<Identifier>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+// `for` loops are turned into `while` loops by the parser
+pub enum Stmt
+{
+    Expression
+    {
+        expr: Expr,
+        span: Span,
+    },
+    Print
+    {
+        expr: Expr,
+        span: Span,
+    },
+    Var
+    {
+        name: Identifier,
+        initializer: Option<Expr>,
+        span: Span,
+    },
+    Block
+    {
+        statements: Vec<Stmt>,
+        span: Span,
+    },
+    If
+    {
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+        span: Span,
+    },
+    While
+    {
+        condition: Expr,
+        body: Box<Stmt>,
+        span: Span,
+    },
+    Function(Function),
+    Return
+    {
+        value: Option<Expr>,
+        span: Span,
+    },
+    Class
+    {
+        name: Identifier,
+        superclass: Option<Identifier>,
+        methods: Vec<Function>,
+        span: Span,
+    },
+    // A token that can't start a statement, only produced by the tolerant parser
+    Error
+    {
+        span: Span,
+    },
+}
+
+impl Stmt
+{
+    pub fn span(&self) -> Span
+    {
+        match self
+        {
+            Stmt::Function(function) => function.span,
+            Stmt::Expression { span, .. }
+            | Stmt::Print { span, .. }
+            | Stmt::Var { span, .. }
+            | Stmt::Block { span, .. }
+            | Stmt::If { span, .. }
+            | Stmt::While { span, .. }
+            | Stmt::Return { span, .. }
+            | Stmt::Class { span, .. }
+            | Stmt::Error { span } => *span,
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use super::error::*;
+use super::scanner::Span;
 use super::value::*;
 use std::io::{self, Write};
 
@@ -11,6 +12,8 @@ pub enum OpCode
     Multiply,
     Divide,
     Negate,
+    Print,
+    Pop,
     Return,
 }
 
@@ -19,36 +22,61 @@ pub enum Instruction
 {
     Constant
     {
-        op: OpCode, line: u32, offset: u32
+        op: OpCode, span: Span, offset: u32
     },
     Add
     {
-        op: OpCode, line: u32
+        op: OpCode, span: Span
     },
     Subtract
     {
-        op: OpCode, line: u32
+        op: OpCode, span: Span
     },
     Multiply
     {
-        op: OpCode, line: u32
+        op: OpCode, span: Span
     },
     Divide
     {
-        op: OpCode, line: u32
+        op: OpCode, span: Span
     },
     Negate
     {
-        op: OpCode, line: u32
+        op: OpCode, span: Span
+    },
+    Print
+    {
+        op: OpCode, span: Span
+    },
+    // Discards the value of an expression statement
+    Pop
+    {
+        op: OpCode, span: Span
     },
     Return
     {
-        op: OpCode, line: u32
+        op: OpCode, span: Span
     },
 }
 
 impl Instruction
 {
+    pub fn span(&self) -> Span
+    {
+        match *self
+        {
+            Instruction::Constant { span, .. }
+            | Instruction::Add { span, .. }
+            | Instruction::Subtract { span, .. }
+            | Instruction::Multiply { span, .. }
+            | Instruction::Divide { span, .. }
+            | Instruction::Negate { span, .. }
+            | Instruction::Print { span, .. }
+            | Instruction::Pop { span, .. }
+            | Instruction::Return { span, .. } => span,
+        }
+    }
+
     // For simple instructions that don't have
     // anything besides their names displayed
     pub fn display_simple(
@@ -104,33 +132,44 @@ impl fmt::Display for Chunk
             {
                 Instruction::Constant {
                     op: _,
-                    line,
+                    span,
                     offset,
-                } => Instruction::display_constant(&self.constants, f, prev_line, *line, *offset)
-                    .unwrap(),
-                Instruction::Add { op: _, line } =>
+                } =>
+                {
+                    Instruction::display_constant(&self.constants, f, prev_line, span.line, *offset)
+                        .unwrap()
+                }
+                Instruction::Add { op: _, span } =>
+                {
+                    Instruction::display_simple(f, prev_line, span.line, "OP_ADD").unwrap()
+                }
+                Instruction::Subtract { op: _, span } =>
+                {
+                    Instruction::display_simple(f, prev_line, span.line, "OP_SUBTRACT").unwrap()
+                }
+                Instruction::Multiply { op: _, span } =>
                 {
-                    Instruction::display_simple(f, prev_line, *line, "OP_ADD").unwrap()
+                    Instruction::display_simple(f, prev_line, span.line, "OP_MULTIPLY").unwrap()
                 }
-                Instruction::Subtract { op: _, line } =>
+                Instruction::Divide { op: _, span } =>
                 {
-                    Instruction::display_simple(f, prev_line, *line, "OP_SUBTRACT").unwrap()
+                    Instruction::display_simple(f, prev_line, span.line, "OP_DIVIDE").unwrap()
                 }
-                Instruction::Multiply { op: _, line } =>
+                Instruction::Negate { op: _, span } =>
                 {
-                    Instruction::display_simple(f, prev_line, *line, "OP_MULTIPLY").unwrap()
+                    Instruction::display_simple(f, prev_line, span.line, "OP_NEGATE").unwrap()
                 }
-                Instruction::Divide { op: _, line } =>
+                Instruction::Print { op: _, span } =>
                 {
-                    Instruction::display_simple(f, prev_line, *line, "OP_DIVIDE").unwrap()
+                    Instruction::display_simple(f, prev_line, span.line, "OP_PRINT").unwrap()
                 }
-                Instruction::Negate { op: _, line } =>
+                Instruction::Pop { op: _, span } =>
                 {
-                    Instruction::display_simple(f, prev_line, *line, "OP_NEGATE").unwrap()
+                    Instruction::display_simple(f, prev_line, span.line, "OP_POP").unwrap()
                 }
-                Instruction::Return { op: _, line } =>
+                Instruction::Return { op: _, span } =>
                 {
-                    Instruction::display_simple(f, prev_line, *line, "OP_RETURN").unwrap()
+                    Instruction::display_simple(f, prev_line, span.line, "OP_RETURN").unwrap()
                 }
             };
         }
@@ -175,7 +214,7 @@ impl Chunk
         Ok(())
     }
 
-    fn print_line(f: &mut Formatter<'_>, line: u32, prev_line: u32) -> Result<(), err::Error>
+    pub fn print_line(f: &mut Formatter<'_>, line: u32, prev_line: u32) -> Result<(), err::Error>
     {
         if line == prev_line
         {
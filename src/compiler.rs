@@ -1,41 +1,174 @@
+use super::ast::*;
+use super::chunk::{Chunk, Instruction, OpCode};
 use super::error::err;
-use super::scanner::{Scanner, TokenKind};
-use std::convert::AsRef;
-use std::io::{self, Write};
+use super::parser;
+use super::scanner::Span;
+use super::value::Value;
+use std::rc::Rc;
 
-pub fn compile(source: String) -> Result<(), err::Error>
+// Returns every syntax error found in the source, not only the
+// first one. The parser recovers at statement boundaries.
+pub fn compile(source: &str) -> Result<Chunk, Vec<err::Error>>
 {
-    let mut scanner = Scanner::new(&source);
+    let statements = parser::parse(source)?;
+    generate(&statements)
+}
+
+// Lowers the syntax tree into stack-based bytecode. Only the
+// statements and expressions the virtual machines can run
+// are supported, the rest is reported with every error.
+// The value of a trailing expression statement is left
+// on the stack, so the REPL can show it.
+pub fn generate(statements: &[Stmt]) -> Result<Chunk, Vec<err::Error>>
+{
+    let mut chunk = Chunk::new();
+    let mut errors = Vec::new();
+
+    for (index, statement) in statements.iter().enumerate()
+    {
+        let result = match statement
+        {
+            Stmt::Expression { expr, .. } if index + 1 == statements.len() =>
+            {
+                expression_code(&mut chunk, expr)
+            }
+            _ => statement_code(&mut chunk, statement),
+        };
+
+        if let Err(e) = result
+        {
+            errors.push(e);
+        }
+    }
 
-    let mut current_line = 0;
-    loop
+    if !errors.is_empty()
     {
-        let token = scanner.scan_token()?;
-        // If tokens are on the same line
-        // it displays the pipe character '|' instead
-        // of a line number
-        if token.line != current_line
+        return Err(errors);
+    }
+
+    Ok(chunk)
+}
+
+fn statement_code(chunk: &mut Chunk, statement: &Stmt) -> Result<(), err::Error>
+{
+    match statement
+    {
+        Stmt::Print { expr, span } =>
         {
-            write!(io::stdout(), "{}", token.line)?;
-            current_line = token.line;
+            expression_code(chunk, expr)?;
+            chunk.write(Instruction::Print {
+                op: OpCode::Print,
+                span: *span,
+            });
         }
-        else
+        Stmt::Expression { expr, span } =>
         {
-            write!(io::stdout(), "{}", "|")?;
+            expression_code(chunk, expr)?;
+            chunk.write(Instruction::Pop {
+                op: OpCode::Pop,
+                span: *span,
+            });
         }
+        _ => return Err(unsupported(statement.span())),
+    }
 
-        write!(
-            io::stdout(),
-            "\t{:14} '{}'\n",
-            token.kind.as_ref(),
-            token.as_str(),
-        )?;
+    Ok(())
+}
 
-        if token.kind == TokenKind::Eof
+fn expression_code(chunk: &mut Chunk, expr: &Expr) -> Result<(), err::Error>
+{
+    match expr
+    {
+        Expr::Literal { value, span } =>
+        {
+            let value = match value
+            {
+                Literal::Number(number) => Value::Double(*number),
+                Literal::Bool(boolean) => Value::Bool(*boolean),
+                Literal::Nil => Value::Nil,
+                Literal::String(string) => Value::String(Rc::from(string.as_str())),
+            };
+            let offset = chunk.add_constant(value);
+            chunk.write(Instruction::Constant {
+                op: OpCode::Constant,
+                span: *span,
+                offset,
+            });
+        }
+        Expr::Grouping { expr, .. } => expression_code(chunk, expr)?,
+        Expr::Unary {
+            operator: UnaryOp::Negate,
+            right,
+            span,
+        } =>
         {
-            break;
+            expression_code(chunk, right)?;
+            chunk.write(Instruction::Negate {
+                op: OpCode::Negate,
+                span: *span,
+            });
         }
+        Expr::Binary {
+            left,
+            operator,
+            right,
+            span,
+        } =>
+        {
+            let span = *span;
+            let instruction = match operator
+            {
+                BinaryOp::Add => Instruction::Add {
+                    op: OpCode::Add,
+                    span,
+                },
+                BinaryOp::Subtract => Instruction::Subtract {
+                    op: OpCode::Subtract,
+                    span,
+                },
+                BinaryOp::Multiply => Instruction::Multiply {
+                    op: OpCode::Multiply,
+                    span,
+                },
+                BinaryOp::Divide => Instruction::Divide {
+                    op: OpCode::Divide,
+                    span,
+                },
+                _ => return Err(unsupported(span)),
+            };
+
+            expression_code(chunk, left)?;
+            expression_code(chunk, right)?;
+            chunk.write(instruction);
+        }
+        _ => return Err(unsupported(expr.span())),
     }
 
     Ok(())
 }
+
+fn unsupported(span: Span) -> err::Error
+{
+    err::Error::new(
+        err::ErrorCode::NotCompilable,
+        "this code can't be compiled to bytecode yet.",
+    )
+    .with_note("the stack and register engines only run numbers, arithmetic and `print`")
+    .with_help("run it with --engine=tree")
+    .at(span)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn code_the_engines_cant_run_is_a_compile_error()
+    {
+        let errors = compile("var x = 1;").err().unwrap();
+
+        assert_eq!(errors[0].code, err::ErrorCode::NotCompilable);
+        assert_eq!(errors[0].kind.exit_code(), 65);
+    }
+}
@@ -0,0 +1,290 @@
+use super::scanner::{LosslessToken, Scanner, TokenKind};
+use super::stream::Lookahead;
+use std::fmt::{self, Formatter};
+use std::iter;
+use std::str;
+use strum_macros::AsRefStr;
+
+// A concrete syntax tree keeps every token and all the trivia,
+// so printing it gives back the exact source code. Tools that
+// rewrite code, like formatters, work on it instead of on the AST.
+
+#[derive(AsRefStr, Copy, Clone, PartialEq)]
+pub enum NodeKind
+{
+    Root,
+    // Ends with a `;` or with a block
+    Statement,
+    // `{ ... }` holding statements
+    Block,
+    // `( ... )`
+    Group,
+}
+
+pub enum Element<'a>
+{
+    Node(Node<'a>),
+    Token(LosslessToken<'a>),
+}
+
+pub struct Node<'a>
+{
+    pub kind: NodeKind,
+    pub children: Vec<Element<'a>>,
+}
+
+impl<'a> Node<'a>
+{
+    fn new(kind: NodeKind) -> Self
+    {
+        Node {
+            kind,
+            children: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Node<'_>
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result
+    {
+        for child in self.children.iter()
+        {
+            write!(f, "{}", child)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Element<'_>
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            Element::Node(node) => write!(f, "{}", node)?,
+            Element::Token(token) =>
+            {
+                for trivia in token.leading.iter()
+                {
+                    write!(f, "{}", as_str(trivia.str))?;
+                }
+                write!(f, "{}", as_str(token.text))?;
+                for trivia in token.trailing.iter()
+                {
+                    write!(f, "{}", as_str(trivia.str))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn as_str(bytes: &[u8]) -> &str
+{
+    // Every slice comes from the source string
+    // and starts and ends at character boundaries
+    unsafe { str::from_utf8_unchecked(bytes) }
+}
+
+struct Parser<'a, I: Iterator<Item = LosslessToken<'a>>>
+{
+    tokens: Lookahead<I>,
+}
+
+// The parser never fails. Unbalanced brackets end their
+// node early or at the end of the source, and stray closing
+// brackets are kept as ordinary tokens.
+pub fn parse(source: &str) -> Node<'_>
+{
+    let mut scanner = Scanner::lossless(source);
+    let mut finished = false;
+    // Tokens up to and including the end of file token
+    let tokens = iter::from_fn(move || {
+        if finished
+        {
+            return None;
+        }
+
+        let token = scanner.scan_lossless();
+        finished = token.token.kind == TokenKind::Eof;
+        Some(token)
+    });
+
+    let mut parser = Parser {
+        tokens: Lookahead::new(tokens),
+    };
+
+    let mut root = Node::new(NodeKind::Root);
+    parser.statements(&mut root, false);
+    // The end of file token holds the trivia at the end of the source
+    parser.push_token(&mut root);
+
+    root
+}
+
+impl<'a, I: Iterator<Item = LosslessToken<'a>>> Parser<'a, I>
+{
+    fn peek(&mut self) -> Option<&TokenKind>
+    {
+        self.tokens.peek(0).map(|token| &token.token.kind)
+    }
+
+    fn push_token(&mut self, node: &mut Node<'a>)
+    {
+        if let Some(token) = self.tokens.next()
+        {
+            node.children.push(Element::Token(token));
+        }
+    }
+
+    fn statements(&mut self, node: &mut Node<'a>, in_block: bool)
+    {
+        loop
+        {
+            match self.peek()
+            {
+                None | Some(TokenKind::Eof) => return,
+                Some(TokenKind::RightBrace) if in_block => return,
+                _ => (),
+            }
+
+            let statement = self.statement(in_block);
+            node.children.push(Element::Node(statement));
+        }
+    }
+
+    fn statement(&mut self, in_block: bool) -> Node<'a>
+    {
+        let mut statement = Node::new(NodeKind::Statement);
+
+        loop
+        {
+            match self.peek()
+            {
+                None | Some(TokenKind::Eof) => break,
+                Some(TokenKind::RightBrace) if in_block => break,
+                Some(TokenKind::Semicolon) =>
+                {
+                    self.push_token(&mut statement);
+                    break;
+                }
+                Some(TokenKind::LeftBrace) =>
+                {
+                    let block = self.block();
+                    statement.children.push(Element::Node(block));
+
+                    // `if` statements continue after the block
+                    if self.peek() != Some(&TokenKind::Else)
+                    {
+                        break;
+                    }
+                }
+                Some(TokenKind::LeftParen) =>
+                {
+                    let group = self.group();
+                    statement.children.push(Element::Node(group));
+                }
+                _ => self.push_token(&mut statement),
+            }
+        }
+
+        statement
+    }
+
+    fn block(&mut self) -> Node<'a>
+    {
+        let mut block = Node::new(NodeKind::Block);
+
+        // The "{"
+        self.push_token(&mut block);
+        self.statements(&mut block, true);
+        if self.peek() == Some(&TokenKind::RightBrace)
+        {
+            self.push_token(&mut block);
+        }
+
+        block
+    }
+
+    fn group(&mut self) -> Node<'a>
+    {
+        let mut group = Node::new(NodeKind::Group);
+
+        // The "("
+        self.push_token(&mut group);
+        loop
+        {
+            match self.peek()
+            {
+                None | Some(TokenKind::Eof) => break,
+                Some(TokenKind::RightParen) =>
+                {
+                    self.push_token(&mut group);
+                    break;
+                }
+                Some(TokenKind::LeftParen) =>
+                {
+                    let inner = self.group();
+                    group.children.push(Element::Node(inner));
+                }
+                Some(TokenKind::LeftBrace) =>
+                {
+                    let block = self.block();
+                    group.children.push(Element::Node(block));
+                }
+                _ => self.push_token(&mut group),
+            }
+        }
+
+        group
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn assert_round_trip(source: &str)
+    {
+        assert_eq!(parse(source).to_string(), source);
+    }
+
+    #[test]
+    fn keeps_trivia()
+    {
+        assert_round_trip("");
+        assert_round_trip("  \t\n\n");
+        assert_round_trip("var x = 1;   // one\n\n\tprint x ;\n");
+        assert_round_trip("fun f(a,b){return a+b;}\nif (true) { print f(1, 2); } else print nil;");
+        assert_round_trip("/// Adds one\nfun inc(n) { return n + 1; }\n");
+        assert_round_trip("print \"a ☃ string\";  // ünïcode\n");
+    }
+
+    #[test]
+    fn keeps_block_comments()
+    {
+        assert_round_trip("/* one */ print /* two\nlines */ 1;");
+        assert_round_trip("/* outer /* inner */ still outer */ print 1;");
+        assert_round_trip("print 1; /* never /* terminated */");
+    }
+
+    #[test]
+    fn keeps_errors()
+    {
+        assert_round_trip("print @ 1 # 2;");
+        assert_round_trip("print \"unterminated\n");
+        assert_round_trip("{ print (1; } )) }");
+        assert_round_trip("0x 1e 1_ 0b102");
+    }
+
+    #[test]
+    fn keeps_crlf()
+    {
+        assert_round_trip("var x = 1;\r\n\r\nprint x;\r\n");
+        assert_round_trip("// comment\r\n/* block\r\n */\r\n");
+    }
+}
@@ -0,0 +1,301 @@
+use super::error::err;
+use super::scanner::Span;
+use std::fmt::{self, Formatter, Write};
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+// How errors are reported to the user
+#[derive(Copy, Clone, PartialEq)]
+pub enum ErrorFormat
+{
+    // Source snippets with carets, for people
+    Human,
+    // One JSON object per error, for editors and CI
+    Json,
+}
+
+impl FromStr for ErrorFormat
+{
+    type Err = err::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err>
+    {
+        match name
+        {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(err::Error::new(
+                err::ErrorCode::InvalidArguments,
+                format!(
+                    "unknown error format '{}'. Expected 'human' or 'json'.",
+                    name
+                ),
+            )),
+        }
+    }
+}
+
+// Prints the error to stderr in the requested format.
+// `file` and `source` are used to show where the error happened.
+pub fn report(error: &err::Error, format: ErrorFormat, file: Option<&str>, source: &str)
+{
+    match format
+    {
+        ErrorFormat::Human => eprint!("{}", Diagnostic::new(error, file, source)),
+        ErrorFormat::Json => eprintln!("{}", to_json(error, file)),
+    }
+}
+
+// An error prepared for displaying to the user
+// together with the source code it points at
+pub struct Diagnostic<'a>
+{
+    pub error: &'a err::Error,
+    pub file: Option<&'a str>,
+    pub source: &'a str,
+    pub colored: bool,
+}
+
+impl<'a> Diagnostic<'a>
+{
+    pub fn new(error: &'a err::Error, file: Option<&'a str>, source: &'a str) -> Self
+    {
+        Diagnostic {
+            error,
+            file,
+            source,
+            colored: io::stderr().is_terminal(),
+        }
+    }
+
+    fn paint(&self, f: &mut Formatter<'_>, color: &str, text: &str) -> fmt::Result
+    {
+        if self.colored
+        {
+            write!(f, "{}{}{}", color, text, RESET)
+        }
+        else
+        {
+            write!(f, "{}", text)
+        }
+    }
+
+    // Warnings are yellow and errors are red
+    fn color(&self) -> &'static str
+    {
+        match self.error.severity
+        {
+            err::Severity::Error => RED,
+            err::Severity::Warning => YELLOW,
+        }
+    }
+
+    // Returns the line the span starts on and
+    // the byte offset of the span inside of it
+    fn source_line(&self, span: Span) -> (&str, usize)
+    {
+        let mut offset = (span.offset as usize).min(self.source.len());
+        while !self.source.is_char_boundary(offset)
+        {
+            offset -= 1;
+        }
+        let start = match self.source[..offset].rfind('\n')
+        {
+            Some(index) => index + 1,
+            None => 0,
+        };
+        let end = match self.source[offset..].find('\n')
+        {
+            Some(index) => offset + index,
+            None => self.source.len(),
+        };
+
+        (
+            self.source[start..end].trim_end_matches('\r'),
+            offset - start,
+        )
+    }
+
+    fn snippet(&self, f: &mut Formatter<'_>, span: Span, gutter: usize) -> fmt::Result
+    {
+        let (line, column) = self.source_line(span);
+
+        self.paint(f, BLUE, &format!("{:>1$} |", "", gutter))?;
+        write!(f, "\n")?;
+        self.paint(f, BLUE, &format!("{} | ", span.line))?;
+        write!(f, "{}\n", line)?;
+
+        // Tabs are kept so the carets line up with the source line
+        let padding: String = line
+            .get(..column)
+            .unwrap_or(line)
+            .chars()
+            .map(|c| match c
+            {
+                '\t' => '\t',
+                _ => ' ',
+            })
+            .collect();
+        // Spans that continue on the next lines
+        // are only underlined up to the end of the first one
+        let length = line
+            .get(column..(column + span.length as usize).min(line.len()))
+            .map_or(0, |text| text.chars().count())
+            .max(1);
+
+        self.paint(f, BLUE, &format!("{:>1$} | ", "", gutter))?;
+        write!(f, "{}", padding)?;
+        self.paint(f, self.color(), &"^".repeat(length))?;
+        write!(f, "\n")?;
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Diagnostic<'_>
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result
+    {
+        let error = self.error;
+        let label = match (error.severity, error.kind)
+        {
+            (err::Severity::Warning, _) => "warning",
+            (_, err::ErrorKind::Runtime) => "runtime error",
+            _ => "error",
+        };
+
+        self.paint(f, self.color(), &format!("{}[{}]", label, error.code))?;
+        self.paint(f, BOLD, &format!(": {}", error.message))?;
+        write!(f, "\n")?;
+
+        // The gutter is as wide as the line number
+        let gutter = match error.span
+        {
+            Some(span) => span.line.to_string().len(),
+            None => 0,
+        };
+
+        if let Some(span) = error.span
+        {
+            self.paint(f, BLUE, &format!("{:>1$}--> ", "", gutter))?;
+            match self.file
+            {
+                Some(file) => write!(f, "{}:{}:{}\n", file, span.line, span.column)?,
+                None => write!(f, "{}:{}\n", span.line, span.column)?,
+            }
+
+            // Errors found while the source was being read
+            // are reported without it
+            if !self.source.is_empty()
+            {
+                self.snippet(f, span, gutter)?;
+            }
+        }
+
+        for note in error.notes.iter()
+        {
+            self.paint(f, BLUE, &format!("{:>1$} = ", "", gutter))?;
+            write!(f, "note: {}\n", note)?;
+        }
+
+        if let Some(help) = &error.help
+        {
+            self.paint(f, BLUE, &format!("{:>1$} = ", "", gutter))?;
+            write!(f, "help: {}\n", help)?;
+        }
+
+        for frame in error.trace.iter()
+        {
+            write!(f, "[line {}] in {}\n", frame.line, frame.function)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Quotes the text and escapes it as a JSON string
+pub fn escape_json(text: &str) -> String
+{
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+
+    for c in text.chars()
+    {
+        match c
+        {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 =>
+            {
+                // Writing to a String never fails
+                write!(escaped, "\\u{:04x}", c as u32).unwrap();
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+pub fn span_to_json(span: Span) -> String
+{
+    format!(
+        "{{\"offset\":{},\"line\":{},\"column\":{},\"length\":{}}}",
+        span.offset, span.line, span.column, span.length
+    )
+}
+
+// Serializes the error as a single line of JSON
+pub fn to_json(error: &err::Error, file: Option<&str>) -> String
+{
+    let file = match file
+    {
+        Some(file) => escape_json(file),
+        None => String::from("null"),
+    };
+    let span = match error.span
+    {
+        Some(span) => span_to_json(span),
+        None => String::from("null"),
+    };
+    let help = match &error.help
+    {
+        Some(help) => escape_json(help),
+        None => String::from("null"),
+    };
+    let notes: Vec<String> = error.notes.iter().map(|note| escape_json(note)).collect();
+    let trace: Vec<String> = error
+        .trace
+        .iter()
+        .map(|frame| {
+            format!(
+                "{{\"function\":{},\"line\":{}}}",
+                escape_json(&frame.function),
+                frame.line
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\"kind\":{},\"code\":\"{}\",\"severity\":{},\"message\":{},\"file\":{},\"span\":{},\"notes\":[{}],\"help\":{},\"trace\":[{}]}}",
+        escape_json(error.kind.as_str()),
+        error.code,
+        escape_json(error.severity.as_str()),
+        escape_json(&error.message),
+        file,
+        span,
+        notes.join(","),
+        help,
+        trace.join(","),
+    )
+}
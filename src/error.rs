@@ -1,32 +1,257 @@
 pub mod err
 {
+    use crate::scanner::Span;
     use std::fmt::{self, Formatter};
-    use std::io::{self, Write};
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub enum ErrorKind
+    {
+        Compile,
+        Runtime,
+        Usage,
+        Io,
+    }
+
+    impl ErrorKind
+    {
+        // Name used in machine-readable output
+        pub fn as_str(self) -> &'static str
+        {
+            match self
+            {
+                Self::Compile => "compile",
+                Self::Runtime => "runtime",
+                Self::Usage => "usage",
+                Self::Io => "io",
+            }
+        }
+
+        // The sysexits.h code the process exits with
+        pub fn exit_code(self) -> i32
+        {
+            match self
+            {
+                Self::Usage => 64,
+                Self::Compile => 65,
+                Self::Runtime => 70,
+                Self::Io => 74,
+            }
+        }
+    }
+
+    impl fmt::Display for ErrorKind
+    {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result
+        {
+            match self
+            {
+                Self::Compile => write!(f, "Compile Error"),
+                Self::Runtime => write!(f, "Runtime Error"),
+                Self::Usage => write!(f, "Usage Error"),
+                Self::Io => write!(f, "I/O Error"),
+            }
+        }
+    }
+
+    // Codes are shown to users and parsed by tools,
+    // so existing ones must never be renumbered
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub enum ErrorCode
+    {
+        UnexpectedCharacter = 1,
+        UnterminatedString = 2,
+        TooManyRegisters = 3,
+        InvalidBytecode = 4,
+        StackUnderflow = 5,
+        TypeMismatch = 6,
+        UnknownEngine = 7,
+        InvalidArguments = 8,
+        Io = 9,
+        InvalidEscape = 10,
+        MalformedNumber = 11,
+        NumberTooLarge = 12,
+        UnterminatedComment = 13,
+        ExpectedExpression = 14,
+        ExpectedToken = 15,
+        InvalidAssignmentTarget = 16,
+        TooManyArguments = 17,
+        UndefinedVariable = 18,
+        Unsupported = 19,
+        UnusedVariable = 20,
+        ShadowedVariable = 21,
+        TopLevelReturn = 22,
+        ThisOutsideClass = 23,
+        SuperWithoutSuperclass = 24,
+        UndefinedGlobal = 25,
+        WrongArgumentCount = 26,
+        NotCompilable = 27,
+        SyntaxErrorNode = 28,
+        OutOfRange = 29,
+        AlreadyDeclared = 30,
+    }
+
+    impl ErrorCode
+    {
+        pub fn kind(self) -> ErrorKind
+        {
+            match self
+            {
+                Self::UnexpectedCharacter
+                | Self::UnterminatedString
+                | Self::TooManyRegisters
+                | Self::InvalidBytecode
+                | Self::InvalidEscape
+                | Self::MalformedNumber
+                | Self::NumberTooLarge
+                | Self::UnterminatedComment
+                | Self::ExpectedExpression
+                | Self::ExpectedToken
+                | Self::InvalidAssignmentTarget
+                | Self::TooManyArguments
+                | Self::UnusedVariable
+                | Self::ShadowedVariable
+                | Self::TopLevelReturn
+                | Self::ThisOutsideClass
+                | Self::SuperWithoutSuperclass
+                | Self::UndefinedGlobal
+                | Self::NotCompilable
+                | Self::SyntaxErrorNode
+                | Self::AlreadyDeclared => ErrorKind::Compile,
+                Self::StackUnderflow
+                | Self::TypeMismatch
+                | Self::UndefinedVariable
+                | Self::Unsupported
+                | Self::OutOfRange
+                | Self::WrongArgumentCount => ErrorKind::Runtime,
+                Self::UnknownEngine | Self::InvalidArguments => ErrorKind::Usage,
+                Self::Io => ErrorKind::Io,
+            }
+        }
+    }
+
+    impl fmt::Display for ErrorCode
+    {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result
+        {
+            write!(f, "E{:04}", *self as u32)
+        }
+    }
+
+    // Warnings are reported, but don't stop the program from running
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub enum Severity
+    {
+        Error,
+        Warning,
+    }
+
+    impl Severity
+    {
+        pub fn as_str(self) -> &'static str
+        {
+            match self
+            {
+                Self::Error => "error",
+                Self::Warning => "warning",
+            }
+        }
+    }
+
+    // A single entry of a runtime stack trace
+    #[derive(Debug)]
+    pub struct Frame
+    {
+        pub function: String,
+        pub line: u32,
+    }
 
     #[derive(Debug)]
-    pub enum Error
+    pub struct Error
     {
-        CompileError(String),
-        RuntimeError(String),
+        pub kind: ErrorKind,
+        pub code: ErrorCode,
+        pub severity: Severity,
+        pub message: String,
+        // The source code that caused the error, if it is known
+        pub span: Option<Span>,
+        pub notes: Vec<String>,
+        pub help: Option<String>,
+        // Innermost call first, empty for compile errors
+        pub trace: Vec<Frame>,
     }
 
-    pub fn error(error: self::Error)
+    impl Error
     {
-        // This function should not return anything,
-        // that's why I'm using unwrap() here
-        write!(io::stderr(), "{}\n", error).unwrap();
+        pub fn new<S: Into<String>>(code: ErrorCode, message: S) -> Self
+        {
+            Error {
+                kind: code.kind(),
+                code,
+                severity: Severity::Error,
+                message: message.into(),
+                span: None,
+                notes: Vec::new(),
+                help: None,
+                trace: Vec::new(),
+            }
+        }
+
+        pub fn at(mut self, span: Span) -> Self
+        {
+            self.span = Some(span);
+            self
+        }
+
+        pub fn with_note<S: Into<String>>(mut self, note: S) -> Self
+        {
+            self.notes.push(note.into());
+            self
+        }
+
+        pub fn with_help<S: Into<String>>(mut self, help: S) -> Self
+        {
+            self.help = Some(help.into());
+            self
+        }
+
+        // I/O errors don't say which file they are about
+        pub fn reading(path: &str, error: std::io::Error) -> Self
+        {
+            Error::new(
+                ErrorCode::Io,
+                format!("couldn't read '{}': {}.", path, error),
+            )
+        }
+
+        pub fn into_warning(mut self) -> Self
+        {
+            self.severity = Severity::Warning;
+            self
+        }
+
+        pub fn is_warning(&self) -> bool
+        {
+            self.severity == Severity::Warning
+        }
     }
 
     impl fmt::Display for Error
     {
         fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result
         {
-            match self
+            match self.severity
+            {
+                Severity::Error => write!(f, "{}[{}]", self.kind, self.code)?,
+                Severity::Warning => write!(f, "Warning[{}]", self.code)?,
+            }
+
+            if let Some(span) = self.span
             {
-                Self::CompileError(msg) => write!(f, "Compile Error: {}", msg)?,
-                Self::RuntimeError(msg) => write!(f, "Runtime Error: {}", msg)?,
+                write!(f, " at {}:{}", span.line, span.column)?;
             }
 
+            write!(f, ": {}", self.message)?;
+
             Ok(())
         }
     }
@@ -35,7 +260,15 @@ pub mod err
     {
         fn from(error: std::io::Error) -> Self
         {
-            Error::RuntimeError(error.to_string())
+            Error::new(ErrorCode::Io, error.to_string())
+        }
+    }
+
+    impl From<rustyline::error::ReadlineError> for Error
+    {
+        fn from(error: rustyline::error::ReadlineError) -> Self
+        {
+            Error::new(ErrorCode::Io, error.to_string())
         }
     }
 
@@ -43,7 +276,7 @@ pub mod err
     {
         fn from(error: std::fmt::Error) -> Self
         {
-            Error::RuntimeError(error.to_string())
+            Error::new(ErrorCode::Io, error.to_string())
         }
     }
 }
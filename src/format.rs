@@ -0,0 +1,252 @@
+use super::cst::{self, as_str, Element, Node, NodeKind};
+use super::error::err;
+use super::scanner::{LosslessToken, TokenKind, TriviaKind};
+
+const INDENT: &str = "    ";
+
+// Only the layout of lines is changed. Lines are indented by
+// their nesting, trailing whitespace is removed and blank lines
+// are collapsed, while the code and comments on a line are kept.
+struct Formatter
+{
+    out: String,
+    errors: Vec<err::Error>,
+    at_line_start: bool,
+    blank_lines: u32,
+}
+
+// Fails with the lexer errors when the source can't be scanned
+pub fn format(source: &str) -> Result<String, Vec<err::Error>>
+{
+    let root = cst::parse(source);
+    let mut formatter = Formatter {
+        out: String::new(),
+        errors: Vec::new(),
+        at_line_start: true,
+        blank_lines: 0,
+    };
+
+    for child in root.children.iter()
+    {
+        match child
+        {
+            Element::Node(node) => formatter.statement(node, 0),
+            // The end of file token
+            Element::Token(token) => formatter.token(token, 0, 0),
+        }
+    }
+
+    if !formatter.errors.is_empty()
+    {
+        return Err(formatter.errors);
+    }
+
+    let mut out = formatter.out;
+    out.truncate(out.trim_end().len());
+    if !out.is_empty()
+    {
+        out.push('\n');
+    }
+
+    Ok(out)
+}
+
+impl Formatter
+{
+    // Lines that continue a statement are indented one level deeper
+    fn statement(&mut self, statement: &Node, depth: usize)
+    {
+        let mut continues = false;
+
+        for child in statement.children.iter()
+        {
+            match child
+            {
+                Element::Node(node) if node.kind == NodeKind::Block => self.block(node, depth),
+                Element::Node(node) => self.group(node, depth),
+                Element::Token(token) =>
+                {
+                    let kind = token.token.kind;
+                    let indent = match kind
+                    {
+                        TokenKind::Else => depth,
+                        _ if continues => depth + 1,
+                        _ => depth,
+                    };
+                    self.token(token, indent, indent);
+
+                    // Doc comments are on the lines before the declaration
+                    continues = kind != TokenKind::DocComment;
+                    continue;
+                }
+            }
+
+            continues = true;
+        }
+    }
+
+    fn block(&mut self, block: &Node, depth: usize)
+    {
+        for child in block.children.iter()
+        {
+            match child
+            {
+                Element::Node(node) => self.statement(node, depth + 1),
+                // Comments before the `}` are still inside the block
+                Element::Token(token) if token.token.kind == TokenKind::RightBrace =>
+                {
+                    self.token(token, depth, depth + 1)
+                }
+                Element::Token(token) => self.token(token, depth, depth),
+            }
+        }
+    }
+
+    fn group(&mut self, group: &Node, depth: usize)
+    {
+        for child in group.children.iter()
+        {
+            match child
+            {
+                Element::Node(node) if node.kind == NodeKind::Block => self.block(node, depth + 1),
+                Element::Node(node) => self.group(node, depth + 1),
+                Element::Token(token) => match token.token.kind
+                {
+                    TokenKind::LeftParen | TokenKind::RightParen =>
+                    {
+                        self.token(token, depth, depth + 1)
+                    }
+                    _ => self.token(token, depth + 1, depth + 1),
+                },
+            }
+        }
+    }
+
+    // `comment_indent` is used for comments on their own lines before the token
+    fn token(&mut self, token: &LosslessToken, indent: usize, comment_indent: usize)
+    {
+        if let Some(error) = token.token.to_error()
+        {
+            self.errors.push(error);
+        }
+
+        for trivia in token.leading.iter()
+        {
+            self.trivia(trivia.kind, trivia.str, comment_indent);
+        }
+
+        self.write(as_str(token.text), indent);
+
+        for trivia in token.trailing.iter()
+        {
+            self.trivia(trivia.kind, trivia.str, indent);
+        }
+    }
+
+    fn trivia(&mut self, kind: TriviaKind, text: &[u8], indent: usize)
+    {
+        match kind
+        {
+            TriviaKind::Newline => self.newline(),
+            // Replaced by the indentation at the start of a line
+            TriviaKind::Whitespace if self.at_line_start => (),
+            _ => self.write(as_str(text), indent),
+        }
+    }
+
+    fn write(&mut self, text: &str, indent: usize)
+    {
+        if text.is_empty()
+        {
+            return;
+        }
+
+        if self.at_line_start
+        {
+            for _ in 0..indent
+            {
+                self.out.push_str(INDENT);
+            }
+        }
+
+        self.out.push_str(text);
+        self.at_line_start = false;
+        self.blank_lines = 0;
+    }
+
+    // At most one blank line is kept, and none at the start
+    fn newline(&mut self)
+    {
+        self.out
+            .truncate(self.out.trim_end_matches(&[' ', '\t', '\r'][..]).len());
+
+        if self.at_line_start
+        {
+            if self.blank_lines > 0 || self.out.is_empty()
+            {
+                return;
+            }
+            self.blank_lines += 1;
+        }
+
+        self.out.push('\n');
+        self.at_line_start = true;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn assert_formats(source: &str, expected: &str)
+    {
+        let formatted = format(source).unwrap_or_else(|_| panic!("failed to format {:?}", source));
+        assert_eq!(formatted, expected);
+
+        // Formatting again doesn't change anything
+        assert_eq!(format(&formatted).ok().as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn indents_blocks()
+    {
+        assert_formats(
+            "fun f(n) {\nif (n > 1) {\nreturn n;\n}\n  return 1;\n}\n",
+            "fun f(n) {\n    if (n > 1) {\n        return n;\n    }\n    return 1;\n}\n",
+        );
+        assert_formats(
+            "class A {\n  /// Says hi\n      hi() { print \"hi\"; }\n}",
+            "class A {\n    /// Says hi\n    hi() { print \"hi\"; }\n}\n",
+        );
+    }
+
+    #[test]
+    fn continued_statements_are_indented()
+    {
+        assert_formats(
+            "var x = 1 +\n2;\nif (x)\nprint x;\nelse\nprint nil;\n",
+            "var x = 1 +\n    2;\nif (x)\n    print x;\nelse\n    print nil;\n",
+        );
+    }
+
+    #[test]
+    fn cleans_up_lines()
+    {
+        assert_formats(
+            "\n\nprint 1;   \n\n\n\nprint 2; // two  \n\n",
+            "print 1;\n\nprint 2; // two\n",
+        );
+        assert_formats("print 1;\r\n\r\nprint 2;\r\n", "print 1;\n\nprint 2;\n");
+        assert_formats("/* a\n   b */ print 1;", "/* a\n   b */ print 1;\n");
+        assert_formats("", "");
+    }
+
+    #[test]
+    fn fails_on_lexer_errors()
+    {
+        assert!(format("print @;").is_err());
+        assert!(format("print \"unterminated;").is_err());
+        assert!(format("/* unterminated").is_err());
+    }
+}
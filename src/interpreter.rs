@@ -0,0 +1,338 @@
+use super::ast::*;
+use super::error::err;
+use super::natives::{self, NATIVES};
+use super::scanner::Span;
+use super::value::Value;
+use super::vm;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// Runs the program by walking the syntax tree. It is slow, but
+// simple enough to be the reference that the bytecode engines
+// are checked against.
+pub struct Interpreter
+{
+    // The first scope holds the globals
+    scopes: Vec<HashMap<String, Value>>,
+}
+
+impl Interpreter
+{
+    // Starts with the natives and an empty `args`
+    pub fn init() -> Self
+    {
+        let mut globals: HashMap<String, Value> = NATIVES
+            .iter()
+            .map(|native| (String::from(native.name), Value::Native(*native)))
+            .collect();
+        globals.insert(String::from("args"), natives::args(&[]));
+
+        Interpreter {
+            scopes: vec![globals],
+        }
+    }
+
+    // Returns the value of the last statement
+    // if it is an expression, for the REPL to show
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<Option<Value>, err::Error>
+    {
+        let mut last = None;
+
+        for statement in statements.iter()
+        {
+            let result = match statement
+            {
+                Stmt::Expression { expr, .. } => self.evaluate(expr).map(Some),
+                _ => self.execute(statement).map(|_| None),
+            };
+
+            match result
+            {
+                Ok(value) => last = value,
+                Err(e) => return Err(vm::runtime_error(e, statement.span())),
+            }
+        }
+
+        Ok(last)
+    }
+
+    pub fn globals(&self) -> &HashMap<String, Value>
+    {
+        &self.scopes[0]
+    }
+
+    pub fn define_global(&mut self, name: &str, value: Value)
+    {
+        self.scopes[0].insert(String::from(name), value);
+    }
+
+    fn execute(&mut self, statement: &Stmt) -> Result<(), err::Error>
+    {
+        match statement
+        {
+            Stmt::Expression { expr, .. } =>
+            {
+                self.evaluate(expr)?;
+            }
+            Stmt::Print { expr, .. } =>
+            {
+                let value = self.evaluate(expr)?;
+                write!(io::stdout(), "{}\n", value)?;
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } =>
+            {
+                let value = match initializer
+                {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => Value::Nil,
+                };
+
+                self.define(&name.name, value);
+            }
+            Stmt::Block { statements, .. } =>
+            {
+                self.scopes.push(HashMap::new());
+                let result = statements
+                    .iter()
+                    .try_for_each(|statement| self.execute(statement));
+                self.scopes.pop();
+
+                result?;
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } =>
+            {
+                if !self.evaluate(condition)?.is_falsey()
+                {
+                    self.execute(then_branch)?;
+                }
+                else if let Some(else_branch) = else_branch
+                {
+                    self.execute(else_branch)?;
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } =>
+            {
+                while !self.evaluate(condition)?.is_falsey()
+                {
+                    self.execute(body)?;
+                }
+            }
+            Stmt::Function(function) => return Err(unsupported("functions", function.span)),
+            Stmt::Return { span, .. } => return Err(unsupported("functions", *span)),
+            Stmt::Class { span, .. } => return Err(unsupported("classes", *span)),
+            Stmt::Error { span } => return Err(syntax_error(*span)),
+        };
+
+        Ok(())
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, err::Error>
+    {
+        let value = match expr
+        {
+            Expr::Literal { value, .. } => match value
+            {
+                Literal::Number(number) => Value::Double(*number),
+                Literal::Bool(boolean) => Value::Bool(*boolean),
+                Literal::Nil => Value::Nil,
+                Literal::String(string) => Value::String(Rc::from(string.as_str())),
+            },
+            Expr::Grouping { expr, .. } => self.evaluate(expr)?,
+            Expr::Unary {
+                operator,
+                right,
+                span,
+            } =>
+            {
+                let right = self.evaluate(right)?;
+                match operator
+                {
+                    UnaryOp::Negate => right.try_neg().map_err(|e| e.at(*span))?,
+                    UnaryOp::Not => Value::Bool(right.is_falsey()),
+                }
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                span,
+            } =>
+            {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                binary(left, *operator, right).map_err(|e| e.at(*span))?
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } =>
+            {
+                let left = self.evaluate(left)?;
+                match (operator, left.is_falsey())
+                {
+                    (LogicalOp::Or, false) | (LogicalOp::And, true) => left,
+                    _ => self.evaluate(right)?,
+                }
+            }
+            Expr::Variable { name } => self.get(name)?,
+            Expr::Assign { name, value, .. } =>
+            {
+                let value = self.evaluate(value)?;
+                self.assign(name, value.clone())?;
+                value
+            }
+            Expr::Call {
+                callee,
+                arguments,
+                span,
+            } =>
+            {
+                let callee = self.evaluate(callee)?;
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.evaluate(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                call(callee, &arguments).map_err(|e| e.at(*span))?
+            }
+            // Point at the name, the object before it is fine
+            Expr::Get { name, .. } | Expr::Set { name, .. } =>
+            {
+                return Err(unsupported("properties", name.span))
+            }
+            Expr::Super { method, .. } =>
+            {
+                return Err(unsupported("superclass methods", method.span))
+            }
+            Expr::This { span } => return Err(unsupported("classes", *span)),
+            Expr::Error { span } => return Err(syntax_error(*span)),
+        };
+
+        Ok(value)
+    }
+
+    fn define(&mut self, name: &str, value: Value)
+    {
+        // There is always at least the global scope
+        if let Some(scope) = self.scopes.last_mut()
+        {
+            scope.insert(String::from(name), value);
+        }
+    }
+
+    fn get(&self, name: &Identifier) -> Result<Value, err::Error>
+    {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.name))
+            .cloned()
+            .ok_or_else(|| undefined_variable(name))
+    }
+
+    fn assign(&mut self, name: &Identifier, value: Value) -> Result<(), err::Error>
+    {
+        match self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&name.name))
+        {
+            Some(variable) => *variable = value,
+            None => return Err(undefined_variable(name)),
+        };
+
+        Ok(())
+    }
+}
+
+fn binary(left: Value, operator: BinaryOp, right: Value) -> Result<Value, err::Error>
+{
+    match operator
+    {
+        BinaryOp::Add => left.try_add(right),
+        BinaryOp::Subtract => left.try_sub(right),
+        BinaryOp::Multiply => left.try_mul(right),
+        BinaryOp::Divide => left.try_div(right),
+        BinaryOp::Equal => Ok(Value::Bool(left == right)),
+        BinaryOp::NotEqual => Ok(Value::Bool(left != right)),
+        BinaryOp::Less => left.try_less(right),
+        BinaryOp::LessEqual => left.try_less_equal(right),
+        BinaryOp::Greater => left.try_greater(right),
+        BinaryOp::GreaterEqual => left.try_greater_equal(right),
+    }
+}
+
+// Only natives can be called until functions are supported
+fn call(callee: Value, arguments: &[Value]) -> Result<Value, err::Error>
+{
+    let native = match callee
+    {
+        Value::Native(native) => native,
+        value =>
+        {
+            return Err(err::Error::new(
+                err::ErrorCode::TypeMismatch,
+                format!("can only call functions, not a {}.", value.type_name()),
+            ))
+        }
+    };
+
+    if arguments.len() != native.arity
+    {
+        return Err(err::Error::new(
+            err::ErrorCode::WrongArgumentCount,
+            format!(
+                "'{}' expects {} arguments, but got {}.",
+                native.name,
+                native.arity,
+                arguments.len()
+            ),
+        ));
+    }
+
+    (native.function)(arguments)
+}
+
+fn undefined_variable(name: &Identifier) -> err::Error
+{
+    err::Error::new(
+        err::ErrorCode::UndefinedVariable,
+        format!("Undefined variable '{}'.", name.name),
+    )
+    .at(name.span)
+}
+
+// Error nodes only come from the tolerant parser,
+// whose trees aren't meant to be run
+fn syntax_error(span: Span) -> err::Error
+{
+    err::Error::new(
+        err::ErrorCode::SyntaxErrorNode,
+        "code with syntax errors can't be run.",
+    )
+    .at(span)
+}
+
+// Features that only exist in the syntax so far
+fn unsupported(what: &str, span: Span) -> err::Error
+{
+    err::Error::new(
+        err::ErrorCode::Unsupported,
+        format!("{} are not supported yet.", what),
+    )
+    .with_note("the parser accepts them, but none of the engines can run them")
+    .at(span)
+}
@@ -0,0 +1,30 @@
+// The language itself, used by the `rox` binary.
+// Editors and other tools can use the tolerant
+// parser to get a syntax tree of code with errors.
+
+pub mod ast;
+mod chunk;
+mod compiler;
+mod cst;
+pub mod diagnostic;
+pub mod error;
+pub mod format;
+pub mod interpreter;
+mod natives;
+pub mod parser;
+mod register;
+mod register_vm;
+pub mod repl;
+pub mod resolver;
+pub mod scanner;
+pub mod session;
+mod stream;
+pub mod tokens;
+mod value;
+pub mod vm;
+
+use error::err;
+
+pub use error::err::Error;
+pub use parser::{parse_tolerant, Parse};
+pub use scanner::Span;
@@ -1,77 +1,398 @@
-#![feature(try_trait)]
-#![feature(exclusive_range_pattern)]
+use std::io::{self, IsTerminal, Read, Write};
+use std::{env, fs, process};
 
-mod chunk;
-mod compiler;
-mod error;
-mod scanner;
-mod value;
-mod vm;
+use rox::diagnostic::{self, ErrorFormat};
+use rox::error::err;
+use rox::interpreter::Interpreter;
+use rox::repl::Repl;
+use rox::resolver::{self, Level, Lints};
+use rox::session::Session;
+use rox::tokens::{self, TokenFormat};
+use rox::vm::Engine;
+use rox::{format, parser};
 
-use std::{
-    env, fs,
-    io::{self, Write},
-};
+const HELP: &str = "\
+Usage: rox [command] [options] [path | -e code | -] [args...]
 
-use error::err;
-use std::io::Read;
-use vm::*;
+Commands:
+    run       Run a script, the default when a path is given
+    repl      Start an interactive session, the default without a path
+    check     Report errors and lint warnings without running the script
+    disasm    Print the bytecode of a script
+    tokens    Print the tokens of a script
+    fmt       Print a script with its indentation fixed
 
-fn main()
+The script is read from stdin when the path is '-', or when
+there is no path and stdin isn't a terminal. Arguments after
+the script are passed to it as the `args` list, read with
+`len(args)` and `at(args, index)`.
+
+Options:
+    -e code                         Run code given on the command line
+    --engine=tree|stack|register    Engine used by run, repl and disasm, tree by default
+    --deny=lint, --allow=lint       Change the level of a lint, or of 'all' of them
+    --format=text|json|csv          Output format of tokens
+    --error-format=human|json       Format of the errors written to stderr
+    -h, --help                      Print this help
+    -V, --version                   Print the version
+
+Exits with 65 for compile errors, 70 for runtime errors,
+74 for I/O errors and 64 for invalid arguments.
+";
+
+// Where the source code of the script comes from
+enum Input
+{
+    File(String),
+    Stdin,
+    // Given with `-e`
+    Inline(String),
+}
+
+impl Input
 {
-    if let Err(e) = run(env::args())
+    // Name shown in errors
+    fn name(&self) -> &str
     {
-        println!("{}", e);
+        match self
+        {
+            Input::File(path) => path,
+            Input::Stdin => "<stdin>",
+            Input::Inline(_) => "<command line>",
+        }
+    }
+
+    // For reading the source as it is needed
+    fn reader(self) -> Result<Box<dyn Read>, err::Error>
+    {
+        match self
+        {
+            Input::File(path) => match fs::File::open(&path)
+            {
+                Ok(file) => Ok(Box::new(FileReader { file, path })),
+                Err(e) => Err(err::Error::reading(&path, e)),
+            },
+            Input::Stdin => Ok(Box::new(io::stdin())),
+            Input::Inline(source) => Ok(Box::new(io::Cursor::new(source.into_bytes()))),
+        }
+    }
+
+    fn read(self) -> Result<String, err::Error>
+    {
+        let mut source = String::new();
+        self.reader()?.read_to_string(&mut source)?;
+        Ok(source)
     }
 }
 
-fn run(mut args: env::Args) -> Result<(), err::Error>
+// Adds the path to the errors of a file,
+// which are also found after it is opened
+struct FileReader
 {
-    let mut vm = Vm::init();
+    file: fs::File,
+    path: String,
+}
 
-    if args.len() > 2
+impl Read for FileReader
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
     {
-        return Err(err::Error::RuntimeError("Usage: rox [path]\n".to_string()));
+        self.file.read(buf).map_err(|e| match e.kind()
+        {
+            io::ErrorKind::Interrupted => e,
+            kind => io::Error::new(kind, err::Error::reading(&self.path, e).message),
+        })
     }
+}
 
+#[derive(Copy, Clone, PartialEq)]
+enum Command
+{
+    Run,
+    Repl,
+    Check,
+    Disasm,
+    Tokens,
+    Fmt,
+}
+
+fn main()
+{
     // Skip the first argument
-    args.next();
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    match args.next()
+    // Errors in the arguments are found before
+    // the error format is known
+    let options = match parse_args(args)
     {
-        Some(path) => run_script(path)?,
-        None => repl()?,
-    }
+        Ok(Some(options)) => options,
+        Ok(None) => process::exit(0),
+        Err(e) =>
+        {
+            diagnostic::report(&e, ErrorFormat::Human, None, "");
+            process::exit(e.code.kind().exit_code());
+        }
+    };
+
+    let error_format = options.error_format;
+    let code = match run(options)
+    {
+        Ok(code) => code,
+        Err(e) =>
+        {
+            diagnostic::report(&e, error_format, None, "");
+            e.code.kind().exit_code()
+        }
+    };
 
-    Ok(())
+    process::exit(code);
+}
+
+struct Options
+{
+    // None when it is decided by the input
+    command: Option<Command>,
+    engine: Engine,
+    lints: Lints,
+    token_format: TokenFormat,
+    error_format: ErrorFormat,
+    input: Option<Input>,
+    script_args: Vec<String>,
 }
 
-fn repl() -> Result<(), err::Error>
+// Returns None when there is nothing to run,
+// like after printing the help
+fn parse_args(args: Vec<String>) -> Result<Option<Options>, err::Error>
 {
-    let mut line = String::new();
-    loop
+    let mut args = args.into_iter().peekable();
+    let mut command = match args.peek().map(String::as_str)
     {
-        write!(io::stdout(), "> ")?;
+        Some("run") => Some(Command::Run),
+        Some("repl") => Some(Command::Repl),
+        Some("check") => Some(Command::Check),
+        Some("disasm") => Some(Command::Disasm),
+        Some("tokens") => Some(Command::Tokens),
+        Some("fmt") => Some(Command::Fmt),
+        _ => None,
+    };
+    if command.is_some()
+    {
+        args.next();
+    }
 
-        io::stdin().read_to_string(&mut line)?;
+    let mut engine = Engine::Tree;
+    let mut lints = Lints::default();
+    let mut token_format = TokenFormat::Text;
+    let mut error_format = ErrorFormat::Human;
+    let mut input = None;
+    let mut script_args = Vec::new();
 
-        interpret(line.clone());
+    while let Some(arg) = args.next()
+    {
+        // Everything after the script belongs to it
+        if input.is_some() && (command.is_none() || command == Some(Command::Run))
+        {
+            script_args.push(arg);
+        }
+        else if arg == "-h" || arg == "--help"
+        {
+            print!("{}", HELP);
+            return Ok(None);
+        }
+        else if arg == "-V" || arg == "--version"
+        {
+            println!("rox {}", env!("CARGO_PKG_VERSION"));
+            return Ok(None);
+        }
+        else if arg == "--dump-tokens"
+        {
+            command = command.or(Some(Command::Tokens));
+        }
+        else if let Some(name) = arg.strip_prefix("--format=")
+        {
+            token_format = name.parse()?;
+            command = command.or(Some(Command::Tokens));
+        }
+        else if let Some(name) = arg.strip_prefix("--engine=")
+        {
+            engine = name.parse()?;
+        }
+        else if let Some(name) = arg.strip_prefix("--deny=")
+        {
+            lints.set(name, Level::Deny)?;
+        }
+        else if let Some(name) = arg.strip_prefix("--allow=")
+        {
+            lints.set(name, Level::Allow)?;
+        }
+        else if let Some(name) = arg.strip_prefix("--error-format=")
+        {
+            error_format = name.parse()?;
+        }
+        else if input.is_some()
+        {
+            return Err(usage_error());
+        }
+        else if arg == "-e"
+        {
+            let code = args.next().ok_or_else(|| {
+                err::Error::new(err::ErrorCode::InvalidArguments, "'-e' expects code.")
+            })?;
+            input = Some(Input::Inline(code));
+        }
+        else if arg == "-"
+        {
+            input = Some(Input::Stdin);
+        }
+        else if arg.starts_with('-')
+        {
+            return Err(err::Error::new(
+                err::ErrorCode::InvalidArguments,
+                format!("unknown option '{}'.", arg),
+            )
+            .with_help("run 'rox --help' to see the options"));
+        }
+        else
+        {
+            input = Some(Input::File(arg));
+        }
     }
+
+    Ok(Some(Options {
+        command,
+        engine,
+        lints,
+        token_format,
+        error_format,
+        input,
+        script_args,
+    }))
 }
 
-fn run_script(path: String) -> Result<(), err::Error>
+// Returns the exit code of the process. Errors
+// in the script are reported by the commands.
+fn run(options: Options) -> Result<i32, err::Error>
 {
-    let source = fs::read_to_string(path)?;
-    if let Err(e) = interpret(source)
+    let Options {
+        command,
+        engine,
+        lints,
+        token_format,
+        error_format,
+        mut input,
+        script_args,
+    } = options;
+
+    // Lets the source be piped in without `-`
+    let piped = !io::stdin().is_terminal();
+    let command = match (command, &input)
+    {
+        (Some(command), _) => command,
+        (None, Some(_)) => Command::Run,
+        (None, None) if piped => Command::Run,
+        (None, None) => Command::Repl,
+    };
+
+    if command != Command::Repl && input.is_none() && piped
     {
-        return Err(e);
+        input = Some(Input::Stdin);
     }
 
-    Ok(())
+    if command == Command::Repl
+    {
+        if input.is_some()
+        {
+            return Err(usage_error());
+        }
+
+        Repl::new(Session::new(engine, lints), error_format).run()?;
+        return Ok(0);
+    }
+
+    let input = input.ok_or_else(usage_error)?;
+    let name = String::from(input.name());
+
+    // Tokens are written as the source is read, so large
+    // sources don't have to be loaded into memory first
+    if command == Command::Tokens
+    {
+        let mut out = io::BufWriter::new(io::stdout().lock());
+        let errors = tokens::dump(input.reader()?, token_format, &mut out)?;
+        out.flush()?;
+
+        // Without the source, errors are shown without a snippet
+        errors
+            .iter()
+            .for_each(|e| diagnostic::report(e, error_format, Some(&name), ""));
+        return Ok(exit_code(&errors));
+    }
+
+    let source = input.read()?;
+    let report = |e: &err::Error| diagnostic::report(e, error_format, Some(&name), &source);
+
+    let errors = match command
+    {
+        Command::Run =>
+        {
+            let mut session = Session::new(engine, lints);
+            session.set_args(&script_args);
+            session.run(&source, &report).err().unwrap_or_default()
+        }
+        Command::Check => check(&source, &lints),
+        Command::Disasm =>
+        {
+            let session = Session::new(engine, lints);
+            session
+                .disassemble(&source, &name)
+                .err()
+                .unwrap_or_default()
+        }
+        Command::Fmt => match format::format(&source)
+        {
+            Ok(formatted) =>
+            {
+                print!("{}", formatted);
+                Vec::new()
+            }
+            Err(errors) => errors,
+        },
+        Command::Repl | Command::Tokens => Vec::new(),
+    };
+
+    errors.iter().for_each(report);
+    Ok(exit_code(&errors))
+}
+
+fn usage_error() -> err::Error
+{
+    err::Error::new(
+        err::ErrorCode::InvalidArguments,
+        "Usage: rox [run|repl|check|disasm|tokens|fmt] [options] [path | -e code | -] [args...]",
+    )
+    .with_help("run 'rox --help' for more information")
+}
+
+// Finds every error and warning without running the code,
+// so all of them are reported even if the parser fails
+fn check(source: &str, lints: &Lints) -> Vec<err::Error>
+{
+    let parse = parser::parse_tolerant(source);
+    // The natives are always defined
+    let interpreter = Interpreter::init();
+    let globals = interpreter.globals().keys();
+
+    let mut errors = parse.errors;
+    errors.extend(resolver::resolve(&parse.statements, lints, globals));
+    errors.sort_by_key(|error| error.span.map(|span| span.offset));
+
+    errors
 }
 
-fn interpret(source: String) -> Result<(), err::Error>
+// The code of the first error, warnings don't make the process fail
+fn exit_code(errors: &[err::Error]) -> i32
 {
-    compiler::compile(source)?;
-    Ok(())
+    errors
+        .iter()
+        .find(|error| !error.is_warning())
+        .map_or(0, |error| error.code.kind().exit_code())
 }
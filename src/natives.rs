@@ -0,0 +1,202 @@
+use super::error::err;
+use super::value::{Native, Value};
+use std::env;
+use std::io::{self, Write};
+use std::process;
+use std::rc::Rc;
+
+// Functions every program can call, defined as globals
+pub const NATIVES: [Native; 4] = [
+    Native {
+        name: "len",
+        arity: 1,
+        function: native_len,
+    },
+    Native {
+        name: "at",
+        arity: 2,
+        function: native_at,
+    },
+    Native {
+        name: "env",
+        arity: 1,
+        function: native_env,
+    },
+    Native {
+        name: "exit",
+        arity: 1,
+        function: native_exit,
+    },
+];
+
+// The command-line arguments after the script, as the `args` global
+pub fn args(args: &[String]) -> Value
+{
+    let values = args
+        .iter()
+        .map(|arg| Value::String(Rc::from(arg.as_str())))
+        .collect();
+
+    Value::List(Rc::new(values))
+}
+
+// `len(value)` is the number of items
+// in a list or of characters in a string
+fn native_len(args: &[Value]) -> Result<Value, err::Error>
+{
+    let len = match &args[0]
+    {
+        Value::List(items) => items.len(),
+        Value::String(string) => string.chars().count(),
+        value => return Err(wrong_type("len", "a list or a string", value)),
+    };
+
+    Ok(Value::Double(len as f64))
+}
+
+// `at(list, index)` returns the item at the index, counted from 0
+fn native_at(args: &[Value]) -> Result<Value, err::Error>
+{
+    let items = match &args[0]
+    {
+        Value::List(items) => items,
+        value => return Err(wrong_type("at", "a list", value)),
+    };
+
+    let index = match args[1]
+    {
+        Value::Double(index) if index.fract() == 0.0 => index,
+        ref value => return Err(wrong_type("at", "an integer index", value)),
+    };
+
+    if index < 0.0 || index >= items.len() as f64
+    {
+        return Err(err::Error::new(
+            err::ErrorCode::OutOfRange,
+            format!(
+                "index {} is out of range for a list of {} items.",
+                index,
+                items.len()
+            ),
+        ));
+    }
+
+    Ok(items[index as usize].clone())
+}
+
+// `env(name)` returns the environment variable,
+// or nil when it isn't set or isn't valid unicode
+fn native_env(args: &[Value]) -> Result<Value, err::Error>
+{
+    let name = match &args[0]
+    {
+        Value::String(name) => name,
+        value => return Err(wrong_type("env", "a string", value)),
+    };
+
+    match env::var(name.as_ref())
+    {
+        Ok(value) => Ok(Value::String(Rc::from(value))),
+        Err(_) => Ok(Value::Nil),
+    }
+}
+
+// `exit(code)` ends the process right away. Exit
+// statuses only have 8 bits, so larger codes are rejected.
+fn native_exit(args: &[Value]) -> Result<Value, err::Error>
+{
+    let code = match args[0]
+    {
+        Value::Double(code) if code.fract() == 0.0 => code,
+        ref value => return Err(wrong_type("exit", "an integer", value)),
+    };
+
+    if !(0.0..=255.0).contains(&code)
+    {
+        return Err(err::Error::new(
+            err::ErrorCode::OutOfRange,
+            format!("exit code {} is out of range.", code),
+        )
+        .with_note("exit codes go from 0 to 255"));
+    }
+
+    // What was printed would be lost otherwise
+    io::stdout().flush()?;
+    process::exit(code as i32);
+}
+
+fn wrong_type(name: &str, expected: &str, value: &Value) -> err::Error
+{
+    err::Error::new(
+        err::ErrorCode::TypeMismatch,
+        format!(
+            "'{}' expects {}, but got a {}.",
+            name,
+            expected,
+            value.type_name()
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn list() -> Value
+    {
+        args(&[String::from("a"), String::from("b")])
+    }
+
+    fn number(value: Result<Value, err::Error>) -> f64
+    {
+        match value
+        {
+            Ok(Value::Double(value)) => value,
+            _ => panic!("expected a number"),
+        }
+    }
+
+    #[test]
+    fn len_of_lists_and_strings()
+    {
+        assert_eq!(number(native_len(&[list()])), 2.0);
+        assert_eq!(number(native_len(&[args(&[])])), 0.0);
+        assert_eq!(number(native_len(&[Value::String(Rc::from("☃ é"))])), 3.0);
+
+        let error = native_len(&[Value::Nil]).err().unwrap();
+        assert_eq!(error.code, err::ErrorCode::TypeMismatch);
+    }
+
+    #[test]
+    fn at_reads_one_item()
+    {
+        assert!(native_at(&[list(), Value::Double(0.0)]).unwrap() == Value::String(Rc::from("a")));
+        assert!(native_at(&[list(), Value::Double(1.0)]).unwrap() == Value::String(Rc::from("b")));
+
+        for index in [-1.0, 2.0, 1e10].iter()
+        {
+            let error = native_at(&[list(), Value::Double(*index)]).err().unwrap();
+            assert_eq!(error.code, err::ErrorCode::OutOfRange);
+        }
+
+        for index in [Value::Double(0.5), Value::Nil].iter()
+        {
+            let error = native_at(&[list(), index.clone()]).err().unwrap();
+            assert_eq!(error.code, err::ErrorCode::TypeMismatch);
+        }
+    }
+
+    #[test]
+    fn exit_codes_must_fit_into_a_status()
+    {
+        for code in [256.0, -1.0, 1e10].iter()
+        {
+            let error = native_exit(&[Value::Double(*code)]).err().unwrap();
+            assert_eq!(error.code, err::ErrorCode::OutOfRange);
+        }
+
+        let error = native_exit(&[Value::Double(1.5)]).err().unwrap();
+        assert_eq!(error.code, err::ErrorCode::TypeMismatch);
+    }
+}
@@ -0,0 +1,939 @@
+use super::ast::*;
+use super::error::err;
+use super::scanner::{Scanner, Span, Token, TokenKind};
+
+// Calls and functions are limited like in clox,
+// so the bytecode can use a single byte for the count
+const MAX_ARGUMENTS: usize = 255;
+
+struct Parser<'a>
+{
+    scanner: Scanner<'a>,
+    current: Token<'a>,
+    previous: Token<'a>,
+    errors: Vec<err::Error>,
+    // Set after an error is reported and cleared once
+    // the parser gets back to the start of a statement
+    panic_mode: bool,
+    // Keeps going after errors instead of skipping the statement
+    tolerant: bool,
+}
+
+// A syntax tree that may contain error nodes,
+// together with every error found while parsing
+pub struct Parse
+{
+    pub statements: Vec<Stmt>,
+    pub errors: Vec<err::Error>,
+}
+
+// Returns every error found in the source,
+// not only the first one
+pub fn parse(source: &str) -> Result<Vec<Stmt>, Vec<err::Error>>
+{
+    let parse = Parser::new(source, false).program();
+    if !parse.errors.is_empty()
+    {
+        return Err(parse.errors);
+    }
+
+    Ok(parse.statements)
+}
+
+// For editors, which need a tree even for broken code. Missing
+// tokens are inserted, expressions that can't be parsed become
+// `Expr::Error` and tokens that can't start a statement become
+// `Stmt::Error`. One error is reported per statement at most.
+pub fn parse_tolerant(source: &str) -> Parse
+{
+    Parser::new(source, true).program()
+}
+
+impl<'a> Parser<'a>
+{
+    fn new(source: &'a str, tolerant: bool) -> Self
+    {
+        let eof = Token {
+            kind: TokenKind::Eof,
+            str: &[],
+            span: Span::default(),
+        };
+
+        Parser {
+            scanner: Scanner::new(source),
+            current: eof,
+            previous: eof,
+            errors: Vec::new(),
+            panic_mode: false,
+            tolerant,
+        }
+    }
+
+    fn program(mut self) -> Parse
+    {
+        let mut statements = Vec::new();
+
+        self.advance();
+        while !self.check(TokenKind::Eof)
+        {
+            if let Some(statement) = self.declaration()
+            {
+                statements.push(statement);
+            }
+        }
+
+        Parse {
+            statements,
+            errors: self.errors,
+        }
+    }
+
+    fn advance(&mut self)
+    {
+        self.previous = self.current;
+
+        loop
+        {
+            match self.scanner.next()
+            {
+                Some(Ok(token)) if token.kind == TokenKind::DocComment => continue,
+                Some(Ok(token)) =>
+                {
+                    self.current = token;
+                    break;
+                }
+                // Reported even while recovering from a syntax error,
+                // since the tokens skipped by `synchronize` come from here
+                Some(Err(error)) =>
+                {
+                    self.errors.push(error);
+                    self.panic_mode = true;
+                }
+                // The end of file token stays current
+                None => break,
+            }
+        }
+    }
+
+    fn check(&self, kind: TokenKind) -> bool
+    {
+        self.current.kind == kind
+    }
+
+    fn match_token(&mut self, kind: TokenKind) -> bool
+    {
+        if !self.check(kind)
+        {
+            return false;
+        }
+
+        self.advance();
+        true
+    }
+
+    fn consume(&mut self, kind: TokenKind, message: &str) -> Result<Token<'a>, err::Error>
+    {
+        if self.check(kind)
+        {
+            self.advance();
+            return Ok(self.previous);
+        }
+
+        let error = err::Error::new(err::ErrorCode::ExpectedToken, message).at(self.current.span);
+        self.fail(error)?;
+
+        // Pretend the missing token is right before the current one
+        Ok(Token {
+            kind,
+            str: &[],
+            span: Span {
+                length: 0,
+                ..self.current.span
+            },
+        })
+    }
+
+    // Stops parsing the statement, unless the parser is tolerant,
+    // in which case the error is reported and parsing goes on
+    fn fail(&mut self, error: err::Error) -> Result<(), err::Error>
+    {
+        if !self.tolerant
+        {
+            return Err(error);
+        }
+
+        self.error(error);
+        Ok(())
+    }
+
+    fn identifier(&mut self, message: &str) -> Result<Identifier, err::Error>
+    {
+        let token = self.consume(TokenKind::Identifier, message)?;
+
+        Ok(Identifier {
+            name: String::from(token.as_str()),
+            span: token.span,
+        })
+    }
+
+    fn error(&mut self, error: err::Error)
+    {
+        if self.panic_mode
+        {
+            return;
+        }
+
+        self.panic_mode = true;
+        self.errors.push(error);
+    }
+
+    // Skips tokens until the start of the next statement
+    fn synchronize(&mut self)
+    {
+        while !self.check(TokenKind::Eof)
+        {
+            match self.current.kind
+            {
+                TokenKind::Semicolon =>
+                {
+                    self.advance();
+                    break;
+                }
+                TokenKind::Class
+                | TokenKind::Fun
+                | TokenKind::Var
+                | TokenKind::For
+                | TokenKind::If
+                | TokenKind::While
+                | TokenKind::Print
+                | TokenKind::Return => break,
+                _ => self.advance(),
+            }
+        }
+
+        self.panic_mode = false;
+    }
+
+    fn declaration(&mut self) -> Option<Stmt>
+    {
+        let start = self.current;
+        let result = if self.match_token(TokenKind::Class)
+        {
+            self.class_declaration()
+        }
+        else if self.match_token(TokenKind::Fun)
+        {
+            self.function().map(Stmt::Function)
+        }
+        else if self.match_token(TokenKind::Var)
+        {
+            self.var_declaration()
+        }
+        else
+        {
+            self.statement()
+        };
+
+        match result
+        {
+            // A tolerant parser has to skip tokens that can't be parsed
+            // itself, or it would try to parse them again forever
+            Ok(_)
+                if self.current.span.offset == start.span.offset && !self.check(TokenKind::Eof) =>
+            {
+                self.advance();
+                self.panic_mode = false;
+                Some(Stmt::Error { span: start.span })
+            }
+            Ok(statement) =>
+            {
+                self.panic_mode = false;
+                Some(statement)
+            }
+            Err(error) =>
+            {
+                self.error(error);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, err::Error>
+    {
+        let start = self.previous.span;
+        let name = self.identifier("Expect class name.")?;
+
+        let superclass = if self.match_token(TokenKind::Less)
+        {
+            Some(self.identifier("Expect superclass name.")?)
+        }
+        else
+        {
+            None
+        };
+
+        self.consume(TokenKind::LeftBrace, "Expect '{' before class body.")?;
+        let mut methods = Vec::new();
+        while !self.check(TokenKind::RightBrace) && !self.check(TokenKind::Eof)
+        {
+            let start = self.current.span.offset;
+            methods.push(self.function()?);
+
+            // Only a tolerant parser gets here without consuming anything
+            if self.current.span.offset == start
+            {
+                self.advance();
+            }
+        }
+        let end = self.consume(TokenKind::RightBrace, "Expect '}' after class body.")?;
+
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            methods,
+            span: start.to(end.span),
+        })
+    }
+
+    // Functions and methods, after the `fun` keyword
+    fn function(&mut self) -> Result<Function, err::Error>
+    {
+        let name = self.identifier("Expect function name.")?;
+        let start = name.span;
+
+        self.consume(TokenKind::LeftParen, "Expect '(' after function name.")?;
+        let mut params = Vec::new();
+        if !self.check(TokenKind::RightParen)
+        {
+            loop
+            {
+                if params.len() == MAX_ARGUMENTS
+                {
+                    self.fail(self.too_many_arguments("parameters"))?;
+                }
+
+                params.push(self.identifier("Expect parameter name.")?);
+                if !self.match_token(TokenKind::Comma)
+                {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenKind::RightParen, "Expect ')' after parameters.")?;
+
+        self.consume(TokenKind::LeftBrace, "Expect '{' before function body.")?;
+        let body = self.block()?;
+
+        Ok(Function {
+            name,
+            params,
+            body,
+            span: start.to(self.previous.span),
+        })
+    }
+
+    fn too_many_arguments(&self, what: &str) -> err::Error
+    {
+        err::Error::new(
+            err::ErrorCode::TooManyArguments,
+            format!("Can't have more than {} {}.", MAX_ARGUMENTS, what),
+        )
+        .at(self.current.span)
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, err::Error>
+    {
+        let start = self.previous.span;
+        let name = self.identifier("Expect variable name.")?;
+
+        let initializer = if self.match_token(TokenKind::Equal)
+        {
+            Some(self.expression()?)
+        }
+        else
+        {
+            None
+        };
+
+        let end = self.consume(
+            TokenKind::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+
+        Ok(Stmt::Var {
+            name,
+            initializer,
+            span: start.to(end.span),
+        })
+    }
+
+    fn statement(&mut self) -> Result<Stmt, err::Error>
+    {
+        let start = self.current.span;
+
+        if self.match_token(TokenKind::Print)
+        {
+            let expr = self.expression()?;
+            let end = self.consume(TokenKind::Semicolon, "Expect ';' after value.")?;
+
+            Ok(Stmt::Print {
+                expr,
+                span: start.to(end.span),
+            })
+        }
+        else if self.match_token(TokenKind::If)
+        {
+            self.if_statement()
+        }
+        else if self.match_token(TokenKind::While)
+        {
+            self.while_statement()
+        }
+        else if self.match_token(TokenKind::For)
+        {
+            self.for_statement()
+        }
+        else if self.match_token(TokenKind::Return)
+        {
+            let value = if self.check(TokenKind::Semicolon)
+            {
+                None
+            }
+            else
+            {
+                Some(self.expression()?)
+            };
+            let end = self.consume(TokenKind::Semicolon, "Expect ';' after return value.")?;
+
+            Ok(Stmt::Return {
+                value,
+                span: start.to(end.span),
+            })
+        }
+        else if self.match_token(TokenKind::LeftBrace)
+        {
+            let statements = self.block()?;
+
+            Ok(Stmt::Block {
+                statements,
+                span: start.to(self.previous.span),
+            })
+        }
+        else
+        {
+            let expr = self.expression()?;
+            let end = self.consume(TokenKind::Semicolon, "Expect ';' after expression.")?;
+
+            Ok(Stmt::Expression {
+                expr,
+                span: start.to(end.span),
+            })
+        }
+    }
+
+    // The statements of a block, after the "{"
+    fn block(&mut self) -> Result<Vec<Stmt>, err::Error>
+    {
+        let mut statements = Vec::new();
+
+        while !self.check(TokenKind::RightBrace) && !self.check(TokenKind::Eof)
+        {
+            if let Some(statement) = self.declaration()
+            {
+                statements.push(statement);
+            }
+        }
+
+        self.consume(TokenKind::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, err::Error>
+    {
+        let start = self.previous.span;
+
+        self.consume(TokenKind::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenKind::RightParen, "Expect ')' after condition.")?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.match_token(TokenKind::Else)
+        {
+            Some(Box::new(self.statement()?))
+        }
+        else
+        {
+            None
+        };
+
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+            span: start.to(self.previous.span),
+        })
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, err::Error>
+    {
+        let start = self.previous.span;
+
+        self.consume(TokenKind::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenKind::RightParen, "Expect ')' after condition.")?;
+        let body = Box::new(self.statement()?);
+
+        Ok(Stmt::While {
+            condition,
+            body,
+            span: start.to(self.previous.span),
+        })
+    }
+
+    // `for (init; condition; increment) body` becomes
+    // `{ init; while (condition) { body; increment; } }`
+    fn for_statement(&mut self) -> Result<Stmt, err::Error>
+    {
+        let start = self.previous.span;
+
+        self.consume(TokenKind::LeftParen, "Expect '(' after 'for'.")?;
+        let initializer = if self.match_token(TokenKind::Semicolon)
+        {
+            None
+        }
+        else if self.match_token(TokenKind::Var)
+        {
+            Some(self.var_declaration()?)
+        }
+        else
+        {
+            let expr = self.expression()?;
+            let end = self.consume(TokenKind::Semicolon, "Expect ';' after expression.")?;
+            let span = expr.span().to(end.span);
+
+            Some(Stmt::Expression { expr, span })
+        };
+
+        let condition = if self.check(TokenKind::Semicolon)
+        {
+            Expr::Literal {
+                value: Literal::Bool(true),
+                span: self.current.span,
+            }
+        }
+        else
+        {
+            self.expression()?
+        };
+        self.consume(TokenKind::Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = if self.check(TokenKind::RightParen)
+        {
+            None
+        }
+        else
+        {
+            Some(self.expression()?)
+        };
+        self.consume(TokenKind::RightParen, "Expect ')' after for clauses.")?;
+
+        let mut body = self.statement()?;
+        let span = start.to(self.previous.span);
+
+        if let Some(increment) = increment
+        {
+            let increment = Stmt::Expression {
+                span: increment.span(),
+                expr: increment,
+            };
+            body = Stmt::Block {
+                statements: vec![body, increment],
+                span,
+            };
+        }
+
+        let mut statement = Stmt::While {
+            condition,
+            body: Box::new(body),
+            span,
+        };
+
+        if let Some(initializer) = initializer
+        {
+            statement = Stmt::Block {
+                statements: vec![initializer, statement],
+                span,
+            };
+        }
+
+        Ok(statement)
+    }
+
+    fn expression(&mut self) -> Result<Expr, err::Error>
+    {
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> Result<Expr, err::Error>
+    {
+        let expr = self.or()?;
+
+        if !self.match_token(TokenKind::Equal)
+        {
+            return Ok(expr);
+        }
+
+        let equals = self.previous.span;
+        let value = Box::new(self.assignment()?);
+        let span = expr.span().to(value.span());
+
+        match expr
+        {
+            Expr::Variable { name } => Ok(Expr::Assign { name, value, span }),
+            Expr::Get { object, name, .. } => Ok(Expr::Set {
+                object,
+                name,
+                value,
+                span,
+            }),
+            _ =>
+            {
+                // The parser isn't confused, so there is no need to synchronize
+                self.error(
+                    err::Error::new(
+                        err::ErrorCode::InvalidAssignmentTarget,
+                        "Invalid assignment target.",
+                    )
+                    .at(equals),
+                );
+                Ok(expr)
+            }
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, err::Error>
+    {
+        let mut expr = self.and()?;
+
+        while self.match_token(TokenKind::Or)
+        {
+            let right = self.and()?;
+            expr = logical(expr, LogicalOp::Or, right);
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, err::Error>
+    {
+        let mut expr = self.equality()?;
+
+        while self.match_token(TokenKind::And)
+        {
+            let right = self.equality()?;
+            expr = logical(expr, LogicalOp::And, right);
+        }
+
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<Expr, err::Error>
+    {
+        let mut expr = self.comparison()?;
+
+        loop
+        {
+            let operator = match self.current.kind
+            {
+                TokenKind::EqualEqual => BinaryOp::Equal,
+                TokenKind::BangEqual => BinaryOp::NotEqual,
+                _ => return Ok(expr),
+            };
+
+            self.advance();
+            let right = self.comparison()?;
+            expr = binary(expr, operator, right);
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, err::Error>
+    {
+        let mut expr = self.term()?;
+
+        loop
+        {
+            let operator = match self.current.kind
+            {
+                TokenKind::Less => BinaryOp::Less,
+                TokenKind::LessEqual => BinaryOp::LessEqual,
+                TokenKind::Greater => BinaryOp::Greater,
+                TokenKind::GreaterEqual => BinaryOp::GreaterEqual,
+                _ => return Ok(expr),
+            };
+
+            self.advance();
+            let right = self.term()?;
+            expr = binary(expr, operator, right);
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, err::Error>
+    {
+        let mut expr = self.factor()?;
+
+        loop
+        {
+            let operator = match self.current.kind
+            {
+                TokenKind::Plus => BinaryOp::Add,
+                TokenKind::Minus => BinaryOp::Subtract,
+                _ => return Ok(expr),
+            };
+
+            self.advance();
+            let right = self.factor()?;
+            expr = binary(expr, operator, right);
+        }
+    }
+
+    fn factor(&mut self) -> Result<Expr, err::Error>
+    {
+        let mut expr = self.unary()?;
+
+        loop
+        {
+            let operator = match self.current.kind
+            {
+                TokenKind::Star => BinaryOp::Multiply,
+                TokenKind::Slash => BinaryOp::Divide,
+                _ => return Ok(expr),
+            };
+
+            self.advance();
+            let right = self.unary()?;
+            expr = binary(expr, operator, right);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, err::Error>
+    {
+        let operator = match self.current.kind
+        {
+            TokenKind::Minus => UnaryOp::Negate,
+            TokenKind::Bang => UnaryOp::Not,
+            _ => return self.call(),
+        };
+
+        self.advance();
+        let start = self.previous.span;
+        let right = Box::new(self.unary()?);
+
+        Ok(Expr::Unary {
+            operator,
+            span: start.to(right.span()),
+            right,
+        })
+    }
+
+    fn call(&mut self) -> Result<Expr, err::Error>
+    {
+        let mut expr = self.primary()?;
+
+        loop
+        {
+            if self.match_token(TokenKind::LeftParen)
+            {
+                let arguments = self.arguments()?;
+                let end = self.consume(TokenKind::RightParen, "Expect ')' after arguments.")?;
+
+                expr = Expr::Call {
+                    span: expr.span().to(end.span),
+                    callee: Box::new(expr),
+                    arguments,
+                };
+            }
+            else if self.match_token(TokenKind::Dot)
+            {
+                let name = self.identifier("Expect property name after '.'.")?;
+
+                expr = Expr::Get {
+                    span: expr.span().to(name.span),
+                    object: Box::new(expr),
+                    name,
+                };
+            }
+            else
+            {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn arguments(&mut self) -> Result<Vec<Expr>, err::Error>
+    {
+        let mut arguments = Vec::new();
+
+        if self.check(TokenKind::RightParen)
+        {
+            return Ok(arguments);
+        }
+
+        loop
+        {
+            if arguments.len() == MAX_ARGUMENTS
+            {
+                self.fail(self.too_many_arguments("arguments"))?;
+            }
+
+            arguments.push(self.expression()?);
+            if !self.match_token(TokenKind::Comma)
+            {
+                return Ok(arguments);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, err::Error>
+    {
+        let token = self.current;
+        let literal = |value| {
+            Ok(Expr::Literal {
+                value,
+                span: token.span,
+            })
+        };
+
+        let expr = match token.kind
+        {
+            TokenKind::False => literal(Literal::Bool(false)),
+            TokenKind::True => literal(Literal::Bool(true)),
+            TokenKind::Nil => literal(Literal::Nil),
+            TokenKind::Number => literal(Literal::Number(token.number_value())),
+            TokenKind::String => literal(Literal::String(token.string_value())),
+            TokenKind::This => Ok(Expr::This { span: token.span }),
+            TokenKind::Identifier => Ok(Expr::Variable {
+                name: Identifier {
+                    name: String::from(token.as_str()),
+                    span: token.span,
+                },
+            }),
+            TokenKind::Super =>
+            {
+                self.advance();
+                self.consume(TokenKind::Dot, "Expect '.' after 'super'.")?;
+                let method = self.identifier("Expect superclass method name.")?;
+
+                return Ok(Expr::Super {
+                    span: token.span.to(method.span),
+                    method,
+                });
+            }
+            TokenKind::LeftParen =>
+            {
+                self.advance();
+                let expr = Box::new(self.expression()?);
+                let end = self.consume(TokenKind::RightParen, "Expect ')' after expression.")?;
+
+                return Ok(Expr::Grouping {
+                    expr,
+                    span: token.span.to(end.span),
+                });
+            }
+            _ =>
+            {
+                let error =
+                    err::Error::new(err::ErrorCode::ExpectedExpression, "Expect expression.")
+                        .at(token.span);
+                self.fail(error)?;
+
+                // The token is left for the statement to deal with
+                return Ok(Expr::Error { span: token.span });
+            }
+        };
+
+        if expr.is_ok()
+        {
+            self.advance();
+        }
+
+        expr
+    }
+}
+
+fn binary(left: Expr, operator: BinaryOp, right: Expr) -> Expr
+{
+    Expr::Binary {
+        span: left.span().to(right.span()),
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    }
+}
+
+fn logical(left: Expr, operator: LogicalOp, right: Expr) -> Expr
+{
+    Expr::Logical {
+        span: left.span().to(right.span()),
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn codes(source: &str) -> Vec<err::ErrorCode>
+    {
+        match parse(source)
+        {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.iter().map(|error| error.code).collect(),
+        }
+    }
+
+    #[test]
+    fn reports_every_syntax_error()
+    {
+        assert_eq!(
+            codes("print 1 +;\nvar = 2;\nprint (3;"),
+            vec![
+                err::ErrorCode::ExpectedExpression,
+                err::ErrorCode::ExpectedToken,
+                err::ErrorCode::ExpectedToken,
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_lexical_errors_skipped_while_recovering()
+    {
+        assert_eq!(
+            codes("print 1 + ; @ # print 2;"),
+            vec![
+                err::ErrorCode::ExpectedExpression,
+                err::ErrorCode::UnexpectedCharacter,
+                err::ErrorCode::UnexpectedCharacter,
+            ]
+        );
+        assert_eq!(
+            codes("print (1;\n@ print 2;"),
+            vec![
+                err::ErrorCode::ExpectedToken,
+                err::ErrorCode::UnexpectedCharacter,
+            ]
+        );
+    }
+
+    #[test]
+    fn tolerant_parse_keeps_the_statements()
+    {
+        let parse = parse_tolerant("print 1; print (; @ print 2;");
+
+        assert_eq!(parse.statements.len(), 3);
+        assert_eq!(parse.errors.len(), 2);
+    }
+}
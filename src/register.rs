@@ -0,0 +1,465 @@
+use super::chunk::{Chunk, Instruction};
+use super::error::*;
+use super::scanner::Span;
+use super::value::*;
+
+// An operand of a register instruction is either
+// a register or an index into the constants table,
+// so constants don't need a separate load instruction
+#[derive(Copy, Clone)]
+pub enum Operand
+{
+    Register(u8),
+    Constant(u32),
+}
+
+impl fmt::Display for Operand
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            Operand::Register(index) => write!(f, "r{}", index),
+            Operand::Constant(index) => write!(f, "k{}", index),
+        }
+    }
+}
+
+// Three-address instructions: `dest` is always a register
+#[derive(Copy, Clone)]
+pub enum RegisterInstruction
+{
+    Add
+    {
+        span: Span,
+        dest: u8,
+        a: Operand,
+        b: Operand,
+    },
+    Subtract
+    {
+        span: Span,
+        dest: u8,
+        a: Operand,
+        b: Operand,
+    },
+    Multiply
+    {
+        span: Span,
+        dest: u8,
+        a: Operand,
+        b: Operand,
+    },
+    Divide
+    {
+        span: Span,
+        dest: u8,
+        a: Operand,
+        b: Operand,
+    },
+    Negate
+    {
+        span: Span, dest: u8, a: Operand
+    },
+    Print
+    {
+        span: Span, a: Operand
+    },
+    Return
+    {
+        span: Span, value: Option<Operand>
+    },
+}
+
+impl RegisterInstruction
+{
+    pub fn span(&self) -> Span
+    {
+        match *self
+        {
+            RegisterInstruction::Add { span, .. }
+            | RegisterInstruction::Subtract { span, .. }
+            | RegisterInstruction::Multiply { span, .. }
+            | RegisterInstruction::Divide { span, .. }
+            | RegisterInstruction::Negate { span, .. }
+            | RegisterInstruction::Print { span, .. }
+            | RegisterInstruction::Return { span, .. } => span,
+        }
+    }
+
+    pub fn display_binary(
+        f: &mut Formatter,
+        prev_line: u32,
+        line: u32,
+        name: &str,
+        (dest, a, b): (u8, Operand, Operand),
+    ) -> Result<u32, err::Error>
+    {
+        Chunk::print_line(f, line, prev_line)?;
+        write!(f, "{} r{}, {}, {}\n", name, dest, a, b)?;
+        Ok(line)
+    }
+}
+
+// RegisterChunk is a series of register instructions
+// together with the constants they refer to
+#[derive(Default)]
+pub struct RegisterChunk
+{
+    pub code: Vec<RegisterInstruction>,
+    pub constants: Vec<Value>,
+    pub register_count: usize,
+    // What was left on the stack of the stack-based code
+    pub result: Option<Operand>,
+}
+
+impl fmt::Display for RegisterChunk
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result
+    {
+        let mut prev_line: u32 = 0;
+
+        for (index, instruction) in self.code.iter().enumerate()
+        {
+            write!(f, "{:0>4} ", index)?;
+
+            prev_line = match *instruction
+            {
+                RegisterInstruction::Add { span, dest, a, b } =>
+                {
+                    RegisterInstruction::display_binary(
+                        f,
+                        prev_line,
+                        span.line,
+                        "ADD",
+                        (dest, a, b),
+                    )
+                    .unwrap()
+                }
+                RegisterInstruction::Subtract { span, dest, a, b } =>
+                {
+                    RegisterInstruction::display_binary(
+                        f,
+                        prev_line,
+                        span.line,
+                        "SUB",
+                        (dest, a, b),
+                    )
+                    .unwrap()
+                }
+                RegisterInstruction::Multiply { span, dest, a, b } =>
+                {
+                    RegisterInstruction::display_binary(
+                        f,
+                        prev_line,
+                        span.line,
+                        "MUL",
+                        (dest, a, b),
+                    )
+                    .unwrap()
+                }
+                RegisterInstruction::Divide { span, dest, a, b } =>
+                {
+                    RegisterInstruction::display_binary(
+                        f,
+                        prev_line,
+                        span.line,
+                        "DIV",
+                        (dest, a, b),
+                    )
+                    .unwrap()
+                }
+                RegisterInstruction::Negate { span, dest, a } =>
+                {
+                    Chunk::print_line(f, span.line, prev_line).unwrap();
+                    write!(f, "NEG r{}, {}\n", dest, a)?;
+                    span.line
+                }
+                RegisterInstruction::Print { span, a } =>
+                {
+                    Chunk::print_line(f, span.line, prev_line).unwrap();
+                    write!(f, "PRINT {}\n", a)?;
+                    span.line
+                }
+                RegisterInstruction::Return { span, value } =>
+                {
+                    Chunk::print_line(f, span.line, prev_line).unwrap();
+                    match value
+                    {
+                        Some(value) => write!(f, "RET {}\n", value)?,
+                        None => write!(f, "RET\n")?,
+                    }
+                    span.line
+                }
+            };
+        }
+
+        Ok(())
+    }
+}
+
+impl RegisterChunk
+{
+    pub fn disassemble(&self, name: &str)
+    {
+        println!("\n== {} ==\n{}", name, self);
+    }
+}
+
+// Translates stack-based bytecode into register-based bytecode.
+// Every stack slot is assigned its own register, and constants are
+// referenced directly by the instructions that consume them.
+pub fn generate(chunk: &Chunk) -> Result<RegisterChunk, err::Error>
+{
+    let mut generator = Generator {
+        chunk: RegisterChunk {
+            constants: chunk.constants.clone(),
+            ..Default::default()
+        },
+        stack: Vec::new(),
+    };
+
+    for instr in chunk.code.iter()
+    {
+        match *instr
+        {
+            Instruction::Constant { offset, .. } =>
+            {
+                generator.push(Operand::Constant(offset))?;
+            }
+            Instruction::Add { span, .. } =>
+            {
+                let (dest, a, b) = generator.binary()?;
+                generator.emit(RegisterInstruction::Add { span, dest, a, b });
+            }
+            Instruction::Subtract { span, .. } =>
+            {
+                let (dest, a, b) = generator.binary()?;
+                generator.emit(RegisterInstruction::Subtract { span, dest, a, b });
+            }
+            Instruction::Multiply { span, .. } =>
+            {
+                let (dest, a, b) = generator.binary()?;
+                generator.emit(RegisterInstruction::Multiply { span, dest, a, b });
+            }
+            Instruction::Divide { span, .. } =>
+            {
+                let (dest, a, b) = generator.binary()?;
+                generator.emit(RegisterInstruction::Divide { span, dest, a, b });
+            }
+            Instruction::Negate { span, .. } =>
+            {
+                let a = generator.pop()?;
+                let dest = generator.push_register()?;
+                generator.emit(RegisterInstruction::Negate { span, dest, a });
+            }
+            Instruction::Print { span, .. } =>
+            {
+                let a = generator.pop()?;
+                generator.emit(RegisterInstruction::Print { span, a });
+            }
+            // The value is simply forgotten
+            Instruction::Pop { .. } =>
+            {
+                generator.pop()?;
+            }
+            Instruction::Return { span, .. } =>
+            {
+                let value = generator.stack.pop();
+                generator.emit(RegisterInstruction::Return { span, value });
+            }
+        }
+    }
+
+    generator.chunk.result = generator.stack.pop();
+    Ok(generator.chunk)
+}
+
+struct Generator
+{
+    chunk: RegisterChunk,
+    // Mirrors the stack of the stack-based VM at compile time
+    stack: Vec<Operand>,
+}
+
+impl Generator
+{
+    fn emit(&mut self, instruction: RegisterInstruction)
+    {
+        self.chunk.code.push(instruction);
+    }
+
+    fn push(&mut self, operand: Operand) -> Result<(), err::Error>
+    {
+        if self.stack.len() > u8::MAX as usize
+        {
+            return Err(err::Error::new(
+                err::ErrorCode::TooManyRegisters,
+                "too many registers needed in one chunk.",
+            ));
+        }
+
+        self.stack.push(operand);
+        self.chunk.register_count = self.chunk.register_count.max(self.stack.len());
+
+        Ok(())
+    }
+
+    // The result of an operation is stored in the register
+    // that corresponds to the stack slot it would occupy
+    fn push_register(&mut self) -> Result<u8, err::Error>
+    {
+        let dest = self.stack.len() as u8;
+        self.push(Operand::Register(dest))?;
+        Ok(dest)
+    }
+
+    fn pop(&mut self) -> Result<Operand, err::Error>
+    {
+        match self.stack.pop()
+        {
+            Some(operand) => Ok(operand),
+            None => Err(err::Error::new(
+                err::ErrorCode::InvalidBytecode,
+                "failed to generate register code. The stack is empty.",
+            )
+            .with_note("this is a bug in the compiler")),
+        }
+    }
+
+    fn binary(&mut self) -> Result<(u8, Operand, Operand), err::Error>
+    {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        let dest = self.push_register()?;
+        Ok((dest, a, b))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::chunk::OpCode;
+    use crate::register_vm::RegisterVm;
+
+    fn constant(chunk: &mut Chunk, value: f64)
+    {
+        let offset = chunk.add_constant(Value::Double(value));
+        chunk.write(Instruction::Constant {
+            op: OpCode::Constant,
+            span: Span::default(),
+            offset,
+        });
+    }
+
+    // The instructions without their index and line
+    fn listing(chunk: &RegisterChunk) -> Vec<String>
+    {
+        chunk
+            .to_string()
+            .lines()
+            .map(|line| String::from(line[9..].trim()))
+            .collect()
+    }
+
+    fn evaluate(chunk: RegisterChunk) -> f64
+    {
+        match RegisterVm::default().interpret(chunk)
+        {
+            Ok(Some(Value::Double(value))) => value,
+            _ => panic!("expected a number"),
+        }
+    }
+
+    #[test]
+    fn binary_reads_constants_directly()
+    {
+        let mut chunk = Chunk::new();
+        constant(&mut chunk, 1.0);
+        constant(&mut chunk, 2.0);
+        chunk.write(Instruction::Subtract {
+            op: OpCode::Subtract,
+            span: Span::default(),
+        });
+
+        let registers = generate(&chunk).unwrap();
+        assert_eq!(listing(&registers), vec!["SUB r0, k0, k1"]);
+        assert_eq!(evaluate(registers), -1.0);
+    }
+
+    #[test]
+    fn operands_keep_their_order()
+    {
+        // 8 / (4 - 1 * 2) = 4
+        let mut chunk = Chunk::new();
+        constant(&mut chunk, 8.0);
+        constant(&mut chunk, 4.0);
+        constant(&mut chunk, 1.0);
+        constant(&mut chunk, 2.0);
+        chunk.write(Instruction::Multiply {
+            op: OpCode::Multiply,
+            span: Span::default(),
+        });
+        chunk.write(Instruction::Subtract {
+            op: OpCode::Subtract,
+            span: Span::default(),
+        });
+        chunk.write(Instruction::Divide {
+            op: OpCode::Divide,
+            span: Span::default(),
+        });
+        chunk.write(Instruction::Negate {
+            op: OpCode::Negate,
+            span: Span::default(),
+        });
+
+        let registers = generate(&chunk).unwrap();
+        assert_eq!(
+            listing(&registers),
+            vec![
+                "MUL r2, k2, k3",
+                "SUB r1, k1, r2",
+                "DIV r0, k0, r1",
+                "NEG r0, r0",
+            ]
+        );
+        assert_eq!(registers.register_count, 4);
+        assert_eq!(evaluate(registers), -4.0);
+    }
+
+    #[test]
+    fn print_and_pop_leave_no_result()
+    {
+        let mut chunk = Chunk::new();
+        constant(&mut chunk, 1.0);
+        chunk.write(Instruction::Print {
+            op: OpCode::Print,
+            span: Span::default(),
+        });
+        constant(&mut chunk, 2.0);
+        chunk.write(Instruction::Pop {
+            op: OpCode::Pop,
+            span: Span::default(),
+        });
+
+        let registers = generate(&chunk).unwrap();
+        assert_eq!(listing(&registers), vec!["PRINT k0"]);
+        assert!(registers.result.is_none());
+    }
+
+    #[test]
+    fn too_many_registers()
+    {
+        let mut chunk = Chunk::new();
+        for _ in 0..256
+        {
+            constant(&mut chunk, 1.0);
+        }
+        assert!(generate(&chunk).is_ok());
+
+        constant(&mut chunk, 1.0);
+        let error = generate(&chunk).err().unwrap();
+        assert_eq!(error.code, err::ErrorCode::TooManyRegisters);
+    }
+}
@@ -0,0 +1,118 @@
+use super::error::*;
+use super::register::{Operand, RegisterChunk, RegisterInstruction};
+use super::value::Value;
+use super::vm::{self, Vm};
+use std::io::{self, Write};
+
+#[derive(Default)]
+pub struct RegisterVm
+{
+    chunk: RegisterChunk,
+    registers: Vec<Value>,
+    // Prints the registers before every instruction
+    pub trace: bool,
+}
+
+impl RegisterVm
+{
+    pub fn init() -> Self
+    {
+        RegisterVm {
+            trace: Vm::is_backtrace_enabled(),
+            ..Default::default()
+        }
+    }
+
+    // Returns the value of the chunk's result, if it has one
+    pub fn interpret(&mut self, chunk: RegisterChunk) -> Result<Option<Value>, err::Error>
+    {
+        self.registers = vec![Value::Double(0.0); chunk.register_count];
+        self.chunk = chunk;
+        self.run()?;
+
+        Ok(self.chunk.result.map(|operand| self.read(operand)))
+    }
+
+    fn run(&mut self) -> Result<(), err::Error>
+    {
+        let code = self.chunk.code.clone();
+        for instr in code.iter()
+        {
+            if self.trace
+            {
+                self.print_registers()?;
+            }
+
+            if let Err(e) = self.execute(*instr)
+            {
+                return Err(vm::runtime_error(e, instr.span()));
+            }
+        }
+
+        if self.trace
+        {
+            self.chunk.disassemble("backtrace_chunk");
+        }
+
+        Ok(())
+    }
+
+    fn execute(&mut self, instr: RegisterInstruction) -> Result<(), err::Error>
+    {
+        match instr
+        {
+            RegisterInstruction::Add { dest, a, b, .. } =>
+            {
+                self.registers[dest as usize] = self.read(a).try_add(self.read(b))?;
+            }
+            RegisterInstruction::Subtract { dest, a, b, .. } =>
+            {
+                self.registers[dest as usize] = self.read(a).try_sub(self.read(b))?;
+            }
+            RegisterInstruction::Multiply { dest, a, b, .. } =>
+            {
+                self.registers[dest as usize] = self.read(a).try_mul(self.read(b))?;
+            }
+            RegisterInstruction::Divide { dest, a, b, .. } =>
+            {
+                self.registers[dest as usize] = self.read(a).try_div(self.read(b))?;
+            }
+            RegisterInstruction::Negate { dest, a, .. } =>
+            {
+                self.registers[dest as usize] = self.read(a).try_neg()?;
+            }
+            RegisterInstruction::Print { a, .. } =>
+            {
+                write!(io::stdout(), "{}\n", self.read(a))?;
+            }
+            RegisterInstruction::Return { value, .. } => match value
+            {
+                Some(value) => write!(io::stdout(), "{}\n", self.read(value))?,
+                None => write!(io::stdout(), "<empty>\n")?,
+            },
+        };
+
+        Ok(())
+    }
+
+    fn read(&self, operand: Operand) -> Value
+    {
+        match operand
+        {
+            Operand::Register(index) => self.registers[index as usize].clone(),
+            Operand::Constant(index) => self.chunk.constants[index as usize].clone(),
+        }
+    }
+
+    fn print_registers(&self) -> Result<(), err::Error>
+    {
+        write!(io::stdout(), "\t")?;
+        for (index, value) in self.registers.iter().enumerate()
+        {
+            write!(io::stdout(), "[r{}: {}]", index, value)?;
+        }
+        write!(io::stdout(), "\n")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,364 @@
+use super::diagnostic::{self, ErrorFormat};
+use super::error::err;
+use super::parser;
+use super::scanner::{self, Scanner, TokenKind};
+use super::session::Session;
+use super::tokens::{self, TokenFormat};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+const PROMPT: &str = "> ";
+// Shown while a statement continues on the next line
+const CONTINUATION_PROMPT: &str = "... ";
+
+const COMMANDS: [&str; 6] = ["tokens", "dis", "globals", "load", "trace", "time"];
+
+pub struct Repl
+{
+    session: Session,
+    error_format: ErrorFormat,
+}
+
+impl Repl
+{
+    pub fn new(session: Session, error_format: ErrorFormat) -> Self
+    {
+        Repl {
+            session,
+            error_format,
+        }
+    }
+
+    // Runs one statement at a time until the end of the input
+    pub fn run(&mut self) -> Result<(), err::Error>
+    {
+        let mut editor = Editor::<ReplHelper>::new();
+        editor.set_helper(Some(ReplHelper::default()));
+
+        let history = history_path();
+        if let Some(path) = &history
+        {
+            // There is no history file the first time
+            let _ = editor.load_history(path);
+        }
+
+        loop
+        {
+            if let Some(helper) = editor.helper_mut()
+            {
+                helper.globals = self
+                    .session
+                    .interpreter()
+                    .globals()
+                    .keys()
+                    .cloned()
+                    .collect();
+            }
+
+            let source = match read_statement(&mut editor)
+            {
+                Ok(source) => source,
+                // Ctrl-C throws away what was typed
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(e.into()),
+            };
+
+            editor.add_history_entry(source.as_str());
+            // Saved after every line, since `exit()` ends the process
+            // without coming back here. Not being able to save it
+            // shouldn't end the session.
+            if let Some(path) = &history
+            {
+                let _ = editor.save_history(path);
+            }
+
+            match source.trim_start().strip_prefix(':')
+            {
+                Some(command) => self.command(command),
+                None => self.eval(&source, None),
+            }
+        }
+
+        // Ctrl-D, leave the prompt on its own line
+        write!(io::stdout(), "\n")?;
+        Ok(())
+    }
+
+    // Errors are reported and the REPL keeps going
+    fn eval(&mut self, line: &str, file: Option<&str>)
+    {
+        let source = complete_statement(line);
+        let format = self.error_format;
+        let report = |e: &err::Error| diagnostic::report(e, format, file, &source);
+
+        match self.session.run(&source, &report)
+        {
+            Ok(Some(value)) => println!("{}", value),
+            Ok(None) => (),
+            Err(errors) => errors.iter().for_each(report),
+        }
+    }
+}
+
+impl Repl
+{
+    // Commands start with `:` and look at the internals of the session
+    fn command(&mut self, line: &str)
+    {
+        let (name, argument) = match line.find(char::is_whitespace)
+        {
+            Some(index) => (&line[..index], line[index..].trim()),
+            None => (line.trim_end(), ""),
+        };
+
+        let result = match name
+        {
+            "tokens" => self.tokens(argument),
+            "dis" => self.disassemble(argument),
+            "globals" => self.globals(),
+            "load" => self.load(argument),
+            "trace" => self.trace(argument),
+            "time" => self.time(argument),
+            _ =>
+            {
+                let names: Vec<String> = COMMANDS.iter().map(|name| format!(":{}", name)).collect();
+                Err(err::Error::new(
+                    err::ErrorCode::InvalidArguments,
+                    format!("unknown command ':{}'.", name),
+                )
+                .with_help(format!("known commands are {}", names.join(", "))))
+            }
+        };
+
+        if let Err(e) = result
+        {
+            diagnostic::report(&e, self.error_format, None, "");
+        }
+    }
+
+    fn tokens(&mut self, source: &str) -> Result<(), err::Error>
+    {
+        let errors = tokens::dump(
+            source.as_bytes(),
+            TokenFormat::Text,
+            &mut io::stdout().lock(),
+        )?;
+        for e in errors.iter()
+        {
+            diagnostic::report(e, self.error_format, None, source);
+        }
+
+        Ok(())
+    }
+
+    fn disassemble(&mut self, line: &str) -> Result<(), err::Error>
+    {
+        let source = complete_statement(line);
+        if let Err(errors) = self.session.disassemble(&source, "repl")
+        {
+            for e in errors.iter()
+            {
+                diagnostic::report(e, self.error_format, None, &source);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Only the tree engine keeps globals between lines
+    fn globals(&mut self) -> Result<(), err::Error>
+    {
+        let mut globals: Vec<_> = self.session.interpreter().globals().iter().collect();
+        globals.sort_by_key(|(name, _)| *name);
+
+        for (name, value) in globals
+        {
+            write!(
+                io::stdout(),
+                "{}: {} = {}\n",
+                name,
+                value.type_name(),
+                value
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, path: &str) -> Result<(), err::Error>
+    {
+        if path.is_empty()
+        {
+            return Err(missing_argument("load", "a path"));
+        }
+
+        let source = fs::read_to_string(path).map_err(|e| err::Error::reading(path, e))?;
+        self.eval(&source, Some(path));
+        Ok(())
+    }
+
+    fn trace(&mut self, argument: &str) -> Result<(), err::Error>
+    {
+        match argument
+        {
+            "on" => self.session.set_trace(true),
+            "off" => self.session.set_trace(false),
+            _ => return Err(missing_argument("trace", "'on' or 'off'")),
+        }
+
+        Ok(())
+    }
+
+    // The time includes printing the value and reporting errors
+    fn time(&mut self, line: &str) -> Result<(), err::Error>
+    {
+        let start = Instant::now();
+        self.eval(line, None);
+        write!(io::stdout(), "time: {:?}\n", start.elapsed())?;
+
+        Ok(())
+    }
+}
+
+fn missing_argument(command: &str, expected: &str) -> err::Error
+{
+    err::Error::new(
+        err::ErrorCode::InvalidArguments,
+        format!("':{}' expects {}.", command, expected),
+    )
+}
+
+fn history_path() -> Option<PathBuf>
+{
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".rox_history"))
+}
+
+// Keeps reading lines until the brackets are balanced
+fn read_statement(editor: &mut Editor<ReplHelper>) -> Result<String, ReadlineError>
+{
+    let mut source = editor.readline(PROMPT)?;
+
+    while is_incomplete(&source)
+    {
+        source.push('\n');
+        source.push_str(&editor.readline(CONTINUATION_PROMPT)?);
+    }
+
+    Ok(source)
+}
+
+// Brackets that are still open, strings and block
+// comments that aren't terminated continue on the next line
+fn is_incomplete(source: &str) -> bool
+{
+    let mut depth = 0;
+
+    for token in Scanner::new(source)
+    {
+        match token
+        {
+            Ok(token) => match token.kind
+            {
+                TokenKind::LeftParen | TokenKind::LeftBrace => depth += 1,
+                TokenKind::RightParen | TokenKind::RightBrace => depth -= 1,
+                _ => (),
+            },
+            Err(e) => match e.code
+            {
+                err::ErrorCode::UnterminatedString | err::ErrorCode::UnterminatedComment =>
+                {
+                    return true
+                }
+                _ => (),
+            },
+        }
+    }
+
+    depth > 0
+}
+
+// Lets expressions be typed without the `;` at the end
+fn complete_statement(line: &str) -> String
+{
+    let line = line.trim_end();
+    let with_semicolon = format!("{};", line);
+
+    if parser::parse(line).is_err() && parser::parse(&with_semicolon).is_ok()
+    {
+        return with_semicolon;
+    }
+
+    String::from(line)
+}
+
+// Completes keywords and the globals defined so far
+#[derive(Default)]
+struct ReplHelper
+{
+    globals: Vec<String>,
+}
+
+impl Completer for ReplHelper
+{
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)>
+    {
+        // The start of the identifier before the cursor
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| scanner::is_identifier_continue(*c))
+            .last()
+            .map_or(pos, |(index, _)| index);
+        let prefix = &line[start..pos];
+
+        // Commands are only completed at the start of the line
+        if line[..start].trim_start() == ":"
+        {
+            let candidates = COMMANDS
+                .iter()
+                .filter(|name| name.starts_with(prefix))
+                .map(|name| String::from(*name))
+                .collect();
+            return Ok((start, candidates));
+        }
+
+        let mut candidates: Vec<String> = scanner::KEYWORDS
+            .iter()
+            .map(|keyword| keyword.as_ref().to_lowercase())
+            .chain(self.globals.iter().cloned())
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ReplHelper
+{
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
@@ -0,0 +1,646 @@
+use super::ast::*;
+use super::error::err;
+use super::scanner::Span;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+// Problems found by looking at the code without running it.
+// Each one can be allowed, reported as a warning or denied.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Lint
+{
+    UnusedVariable,
+    ShadowedVariable,
+    TopLevelReturn,
+    ThisOutsideClass,
+    SuperWithoutSuperclass,
+    UndefinedGlobal,
+}
+
+const LINTS: [Lint; 6] = [
+    Lint::UnusedVariable,
+    Lint::ShadowedVariable,
+    Lint::TopLevelReturn,
+    Lint::ThisOutsideClass,
+    Lint::SuperWithoutSuperclass,
+    Lint::UndefinedGlobal,
+];
+
+impl Lint
+{
+    // Name used by `--allow` and `--deny`
+    pub fn name(self) -> &'static str
+    {
+        match self
+        {
+            Self::UnusedVariable => "unused-variable",
+            Self::ShadowedVariable => "shadowed-variable",
+            Self::TopLevelReturn => "top-level-return",
+            Self::ThisOutsideClass => "this-outside-class",
+            Self::SuperWithoutSuperclass => "super-without-superclass",
+            Self::UndefinedGlobal => "undefined-global",
+        }
+    }
+
+    fn code(self) -> err::ErrorCode
+    {
+        match self
+        {
+            Self::UnusedVariable => err::ErrorCode::UnusedVariable,
+            Self::ShadowedVariable => err::ErrorCode::ShadowedVariable,
+            Self::TopLevelReturn => err::ErrorCode::TopLevelReturn,
+            Self::ThisOutsideClass => err::ErrorCode::ThisOutsideClass,
+            Self::SuperWithoutSuperclass => err::ErrorCode::SuperWithoutSuperclass,
+            Self::UndefinedGlobal => err::ErrorCode::UndefinedGlobal,
+        }
+    }
+
+    // Code that can never run correctly is denied,
+    // code that is only suspicious is a warning
+    fn default_level(self) -> Level
+    {
+        match self
+        {
+            Self::TopLevelReturn | Self::ThisOutsideClass | Self::SuperWithoutSuperclass =>
+            {
+                Level::Deny
+            }
+            Self::UnusedVariable | Self::ShadowedVariable | Self::UndefinedGlobal => Level::Warn,
+        }
+    }
+}
+
+impl FromStr for Lint
+{
+    type Err = err::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err>
+    {
+        match LINTS.iter().find(|lint| lint.name() == name)
+        {
+            Some(lint) => Ok(*lint),
+            None =>
+            {
+                let names: Vec<&str> = LINTS.iter().map(|lint| lint.name()).collect();
+                Err(err::Error::new(
+                    err::ErrorCode::InvalidArguments,
+                    format!("unknown lint '{}'.", name),
+                )
+                .with_help(format!("known lints are 'all', {}", names.join(", "))))
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Level
+{
+    Allow,
+    Warn,
+    Deny,
+}
+
+pub struct Lints
+{
+    levels: HashMap<Lint, Level>,
+}
+
+impl Default for Lints
+{
+    fn default() -> Self
+    {
+        Lints {
+            levels: LINTS
+                .iter()
+                .map(|lint| (*lint, lint.default_level()))
+                .collect(),
+        }
+    }
+}
+
+impl Lints
+{
+    // `all` sets the level of every lint
+    pub fn set(&mut self, name: &str, level: Level) -> Result<(), err::Error>
+    {
+        if name == "all"
+        {
+            for lint in LINTS.iter()
+            {
+                self.levels.insert(*lint, level);
+            }
+
+            return Ok(());
+        }
+
+        self.levels.insert(name.parse()?, level);
+        Ok(())
+    }
+
+    pub fn level(&self, lint: Lint) -> Level
+    {
+        self.levels
+            .get(&lint)
+            .copied()
+            .unwrap_or_else(|| lint.default_level())
+    }
+}
+
+struct Local
+{
+    span: Span,
+    used: bool,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum FunctionType
+{
+    None,
+    Function,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum ClassType
+{
+    None,
+    Class,
+    Subclass,
+}
+
+struct Resolver<'a>
+{
+    lints: &'a Lints,
+    diagnostics: Vec<err::Error>,
+    // Innermost scope last. Globals aren't kept here.
+    scopes: Vec<HashMap<String, Local>>,
+    globals: HashSet<String>,
+    function: FunctionType,
+    class: ClassType,
+}
+
+// Returns the warnings and errors found in the program.
+// Which lints are reported, and how, is decided by `lints`.
+// `globals` are the ones defined by code that ran before.
+pub fn resolve<'a, I>(statements: &[Stmt], lints: &Lints, globals: I) -> Vec<err::Error>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let mut resolver = Resolver {
+        lints,
+        diagnostics: Vec::new(),
+        scopes: Vec::new(),
+        globals: globals.into_iter().cloned().collect(),
+        function: FunctionType::None,
+        class: ClassType::None,
+    };
+
+    // Functions can use globals that are declared after them
+    for statement in statements.iter()
+    {
+        let name = match statement
+        {
+            Stmt::Var { name, .. } | Stmt::Class { name, .. } => name,
+            Stmt::Function(function) => &function.name,
+            _ => continue,
+        };
+
+        resolver.globals.insert(name.name.clone());
+    }
+
+    resolver.statements(statements);
+
+    // Unused variables are only found at the end of their scope
+    let mut diagnostics = resolver.diagnostics;
+    diagnostics.sort_by_key(|error| error.span.map(|span| span.offset));
+    diagnostics
+}
+
+impl Resolver<'_>
+{
+    fn report(&mut self, lint: Lint, span: Span, message: String)
+    {
+        let error = err::Error::new(lint.code(), message)
+            .at(span)
+            .with_note(format!(
+                "`{}` can be changed with --allow or --deny",
+                lint.name()
+            ));
+
+        match self.lints.level(lint)
+        {
+            Level::Allow => (),
+            Level::Warn => self.diagnostics.push(error.into_warning()),
+            Level::Deny => self.diagnostics.push(error),
+        }
+    }
+
+    fn statements(&mut self, statements: &[Stmt])
+    {
+        for statement in statements.iter()
+        {
+            self.statement(statement);
+        }
+    }
+
+    fn begin_scope(&mut self)
+    {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self)
+    {
+        let scope = match self.scopes.pop()
+        {
+            Some(scope) => scope,
+            None => return,
+        };
+
+        let mut unused: Vec<(String, Local)> = scope
+            .into_iter()
+            .filter(|(name, local)| !local.used && !name.starts_with('_'))
+            .collect();
+        // Report them in the order they appear in
+        unused.sort_by_key(|(_, local)| local.span.offset);
+
+        for (name, local) in unused
+        {
+            let message = format!("Unused variable '{}'.", name);
+            self.report(Lint::UnusedVariable, local.span, message);
+        }
+    }
+
+    fn declare(&mut self, name: &Identifier)
+    {
+        // Names the parser inserted after a syntax error are empty
+        if self.scopes.is_empty() || name.name.is_empty()
+        {
+            return;
+        }
+
+        // The first one is kept, so whether it is used is still known
+        if let Some(local) = self.scopes.last().and_then(|scope| scope.get(&name.name))
+        {
+            let error = err::Error::new(
+                err::ErrorCode::AlreadyDeclared,
+                format!(
+                    "Already a variable named '{}' in this scope, declared on line {}.",
+                    name.name, local.span.line
+                ),
+            )
+            .at(name.span);
+            self.diagnostics.push(error);
+            return;
+        }
+
+        let outer = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.name))
+            .map(|local| local.span.line);
+        if let Some(line) = outer
+        {
+            let message = format!(
+                "Variable '{}' shadows the one declared on line {}.",
+                name.name, line
+            );
+            self.report(Lint::ShadowedVariable, name.span, message);
+        }
+
+        if let Some(scope) = self.scopes.last_mut()
+        {
+            let local = Local {
+                span: name.span,
+                used: false,
+            };
+            scope.insert(name.name.clone(), local);
+        }
+    }
+
+    // Assigning to a variable doesn't count as using it
+    fn resolve_name(&mut self, name: &Identifier, is_read: bool)
+    {
+        if name.name.is_empty()
+        {
+            return;
+        }
+
+        let local = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&name.name));
+
+        match local
+        {
+            Some(local) => local.used |= is_read,
+            None if !self.globals.contains(&name.name) =>
+            {
+                let message = format!("Variable '{}' is never declared.", name.name);
+                self.report(Lint::UndefinedGlobal, name.span, message);
+            }
+            None => (),
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt)
+    {
+        match statement
+        {
+            Stmt::Expression { expr, .. } | Stmt::Print { expr, .. } => self.expression(expr),
+            Stmt::Var {
+                name, initializer, ..
+            } =>
+            {
+                if let Some(initializer) = initializer
+                {
+                    self.expression(initializer);
+                }
+                self.declare(name);
+            }
+            Stmt::Block { statements, .. } =>
+            {
+                self.begin_scope();
+                self.statements(statements);
+                self.end_scope();
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } =>
+            {
+                self.expression(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch
+                {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } =>
+            {
+                self.expression(condition);
+                self.statement(body);
+            }
+            Stmt::Function(function) =>
+            {
+                self.declare(&function.name);
+                self.function(function);
+            }
+            Stmt::Return { value, span } =>
+            {
+                if self.function == FunctionType::None
+                {
+                    let message = String::from("Can't return from top-level code.");
+                    self.report(Lint::TopLevelReturn, *span, message);
+                }
+
+                if let Some(value) = value
+                {
+                    self.expression(value);
+                }
+            }
+            Stmt::Error { .. } => (),
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } =>
+            {
+                let enclosing = self.class;
+                self.class = ClassType::Class;
+                self.declare(name);
+
+                if let Some(superclass) = superclass
+                {
+                    self.class = ClassType::Subclass;
+                    self.resolve_name(superclass, true);
+                }
+
+                for method in methods.iter()
+                {
+                    self.function(method);
+                }
+
+                self.class = enclosing;
+            }
+        }
+    }
+
+    fn function(&mut self, function: &Function)
+    {
+        let enclosing = self.function;
+        self.function = FunctionType::Function;
+
+        self.begin_scope();
+        for param in function.params.iter()
+        {
+            self.declare(param);
+        }
+        self.statements(&function.body);
+        self.end_scope();
+
+        self.function = enclosing;
+    }
+
+    fn expression(&mut self, expr: &Expr)
+    {
+        match expr
+        {
+            Expr::Literal { .. } | Expr::Error { .. } => (),
+            Expr::Grouping { expr, .. } | Expr::Unary { right: expr, .. } => self.expression(expr),
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } =>
+            {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expr::Variable { name } => self.resolve_name(name, true),
+            Expr::Assign { name, value, .. } =>
+            {
+                self.expression(value);
+                self.resolve_name(name, false);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } =>
+            {
+                self.expression(callee);
+                for argument in arguments.iter()
+                {
+                    self.expression(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.expression(object),
+            Expr::Set { object, value, .. } =>
+            {
+                self.expression(value);
+                self.expression(object);
+            }
+            Expr::This { span } =>
+            {
+                if self.class == ClassType::None
+                {
+                    let message = String::from("Can't use 'this' outside of a class.");
+                    self.report(Lint::ThisOutsideClass, *span, message);
+                }
+            }
+            Expr::Super { span, .. } =>
+            {
+                let message = match self.class
+                {
+                    ClassType::None => "Can't use 'super' outside of a class.",
+                    ClassType::Class => "Can't use 'super' in a class with no superclass.",
+                    ClassType::Subclass => return,
+                };
+                self.report(Lint::SuperWithoutSuperclass, *span, String::from(message));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::parser;
+
+    fn check(source: &str, lints: &Lints) -> Vec<err::Error>
+    {
+        let parse = parser::parse_tolerant(source);
+        resolve(&parse.statements, lints, &[])
+    }
+
+    fn codes(errors: &[err::Error]) -> Vec<(err::ErrorCode, bool)>
+    {
+        errors
+            .iter()
+            .map(|error| (error.code, error.is_warning()))
+            .collect()
+    }
+
+    #[test]
+    fn default_levels()
+    {
+        let lints = Lints::default();
+
+        assert_eq!(
+            codes(&check("{ var x = 1; }", &lints)),
+            vec![(err::ErrorCode::UnusedVariable, true)]
+        );
+        assert_eq!(
+            codes(&check(
+                "{ var x = 1; { var x = 2; print x; } print x; }",
+                &lints
+            )),
+            vec![(err::ErrorCode::ShadowedVariable, true)]
+        );
+        assert_eq!(
+            codes(&check("print y;", &lints)),
+            vec![(err::ErrorCode::UndefinedGlobal, true)]
+        );
+        assert_eq!(
+            codes(&check("return 1;", &lints)),
+            vec![(err::ErrorCode::TopLevelReturn, false)]
+        );
+        assert_eq!(
+            codes(&check("print this;", &lints)),
+            vec![(err::ErrorCode::ThisOutsideClass, false)]
+        );
+        assert_eq!(
+            codes(&check("class A { f() { return super.f(); } }", &lints)),
+            vec![(err::ErrorCode::SuperWithoutSuperclass, false)]
+        );
+    }
+
+    #[test]
+    fn levels_can_be_changed()
+    {
+        let mut lints = Lints::default();
+        lints.set("unused-variable", Level::Deny).unwrap();
+        lints.set("top-level-return", Level::Allow).unwrap();
+
+        assert_eq!(
+            codes(&check("{ var x = 1; }", &lints)),
+            vec![(err::ErrorCode::UnusedVariable, false)]
+        );
+        assert!(check("return 1;", &lints).is_empty());
+        assert_eq!(lints.level(Lint::ShadowedVariable), Level::Warn);
+    }
+
+    #[test]
+    fn all_sets_every_lint()
+    {
+        let mut lints = Lints::default();
+        lints.set("all", Level::Allow).unwrap();
+
+        assert!(check("{ var x = 1; } print y; return 1; print this;", &lints).is_empty());
+
+        // Later options override earlier ones
+        lints.set("undefined-global", Level::Deny).unwrap();
+        assert_eq!(
+            codes(&check("print y;", &lints)),
+            vec![(err::ErrorCode::UndefinedGlobal, false)]
+        );
+    }
+
+    #[test]
+    fn unknown_lints_are_rejected()
+    {
+        let error = Lints::default().set("unused", Level::Deny).err().unwrap();
+        assert_eq!(error.code, err::ErrorCode::InvalidArguments);
+    }
+
+    #[test]
+    fn globals_can_be_used_before_they_are_declared()
+    {
+        let lints = Lints::default();
+
+        assert!(check("fun f() { return g(); } fun g() { return 1; }", &lints).is_empty());
+        assert_eq!(check("print clock;", &lints).len(), 1);
+        let globals = [String::from("clock")];
+        let parse = parser::parse_tolerant("print clock;");
+        assert!(resolve(&parse.statements, &lints, &globals).is_empty());
+    }
+
+    #[test]
+    fn redeclaring_in_the_same_scope()
+    {
+        let lints = Lints::default();
+
+        assert_eq!(
+            codes(&check("{ var a = 1; var a = 2; }", &lints)),
+            vec![
+                (err::ErrorCode::UnusedVariable, true),
+                (err::ErrorCode::AlreadyDeclared, false),
+            ]
+        );
+        assert_eq!(
+            codes(&check("fun f(a, a) { return a; }", &lints)),
+            vec![(err::ErrorCode::AlreadyDeclared, false)]
+        );
+        // Globals can be redeclared
+        assert!(check("var a = 1; var a = 2; print a;", &lints).is_empty());
+
+        // Even when every lint is allowed
+        let mut lints = Lints::default();
+        lints.set("all", Level::Allow).unwrap();
+        assert_eq!(
+            codes(&check("{ var a = 1; var a = 2; }", &lints)),
+            vec![(err::ErrorCode::AlreadyDeclared, false)]
+        );
+    }
+
+    #[test]
+    fn names_inserted_by_the_parser_are_ignored()
+    {
+        let lints = Lints::default();
+
+        assert!(check("fun f( { print 1; }", &lints).is_empty());
+        assert!(check("class A { foo }", &lints).is_empty());
+        assert!(check("{ var = 1; }", &lints).is_empty());
+    }
+}
@@ -2,10 +2,15 @@ use super::err;
 use std::cmp::PartialEq;
 use std::str;
 use strum_macros::AsRefStr;
+use unicode_xid::UnicodeXID;
 
-#[derive(AsRefStr, PartialEq)]
+#[derive(AsRefStr, Copy, Clone, Debug, PartialEq)]
 pub enum TokenKind
 {
+    // Source code that couldn't be scanned.
+    // The code says what was wrong with it.
+    Error(err::ErrorCode),
+
     // Single-character tokens.
     LeftParen,
     RightParen,
@@ -52,14 +57,87 @@ pub enum TokenKind
     Var,
     While,
 
+    // `///` comments, kept for documentation.
+    DocComment,
+
     Eof,
 }
 
+pub const KEYWORDS: [TokenKind; 16] = [
+    TokenKind::And,
+    TokenKind::Class,
+    TokenKind::Else,
+    TokenKind::False,
+    TokenKind::For,
+    TokenKind::Fun,
+    TokenKind::If,
+    TokenKind::Nil,
+    TokenKind::Or,
+    TokenKind::Print,
+    TokenKind::Return,
+    TokenKind::Super,
+    TokenKind::This,
+    TokenKind::True,
+    TokenKind::Var,
+    TokenKind::While,
+];
+
+// Location of a piece of source code
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Span
+{
+    // Byte offset from the start of the source
+    pub offset: u32,
+    pub line: u32,
+    // Starts at 1, like lines do
+    pub column: u32,
+    // Length in bytes
+    pub length: u32,
+}
+
+impl Span
+{
+    // A span from the start of this one to the end of `end`.
+    // It is never shorter than this one.
+    pub fn to(self, end: Span) -> Span
+    {
+        let end = (end.offset + end.length).max(self.offset + self.length);
+
+        Span {
+            length: end - self.offset,
+            ..self
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
 pub struct Token<'a>
 {
     pub kind: TokenKind,
     pub str: &'a [u8],
-    pub line: u32,
+    pub span: Span,
+}
+
+// A token that doesn't borrow the source code,
+// for sources that are read in pieces
+pub struct OwnedToken
+{
+    pub kind: TokenKind,
+    pub text: String,
+    pub span: Span,
+}
+
+impl OwnedToken
+{
+    // Borrows it back, to use what works on tokens
+    pub fn as_token(&self) -> Token<'_>
+    {
+        Token {
+            kind: self.kind,
+            str: self.text.as_bytes(),
+            span: self.span,
+        }
+    }
 }
 
 impl Token<'_>
@@ -68,6 +146,128 @@ impl Token<'_>
     {
         unsafe { str::from_utf8_unchecked(self.str) }
     }
+
+    pub fn into_owned(self) -> OwnedToken
+    {
+        OwnedToken {
+            text: String::from(self.as_str()),
+            kind: self.kind,
+            span: self.span,
+        }
+    }
+
+    // Describes what is wrong with an error token.
+    // Returns None for any other kind of token.
+    pub fn to_error(&self) -> Option<err::Error>
+    {
+        let code = match self.kind
+        {
+            TokenKind::Error(code) => code,
+            _ => return None,
+        };
+
+        let error = match code
+        {
+            err::ErrorCode::UnterminatedString => err::Error::new(code, "Unterminated string.")
+                .with_help("add a closing '\"' to end the string"),
+            err::ErrorCode::MalformedNumber => err::Error::new(
+                code,
+                format!("Malformed number literal '{}'.", self.as_str()),
+            )
+            .with_help("numbers look like 42, 1.5, 1e-9, 1_000_000, 0x1F or 0b1010"),
+            err::ErrorCode::NumberTooLarge => err::Error::new(
+                code,
+                format!("Number literal '{}' is too large.", self.as_str()),
+            ),
+            err::ErrorCode::UnterminatedComment =>
+            {
+                err::Error::new(code, "Unterminated block comment.")
+                    .with_help("add a closing '*/'. Nested '/*' need their own '*/' too")
+            }
+            err::ErrorCode::InvalidEscape => err::Error::new(
+                code,
+                format!("Invalid escape sequence '{}'.", self.as_str()),
+            )
+            .with_help("valid escapes are \\n, \\t, \\\\, \\\" and \\u{...}"),
+            _ => err::Error::new(code, "Unexpected character"),
+        };
+
+        Some(error.at(self.span))
+    }
+
+    pub fn number_value(&self) -> f64
+    {
+        // The scanner has already checked the number
+        parse_number(self.as_str()).unwrap_or(f64::NAN)
+    }
+
+    // The contents of a string token with
+    // the quotes removed and escapes replaced
+    pub fn string_value(&self) -> String
+    {
+        let text = self.as_str();
+        let text = &text[1..text.len() - 1];
+        let mut value = String::with_capacity(text.len());
+
+        let mut chars = text.chars();
+        while let Some(c) = chars.next()
+        {
+            if c != '\\'
+            {
+                value.push(c);
+                continue;
+            }
+
+            // The scanner has already checked the escapes
+            match chars.next()
+            {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('u') =>
+                {
+                    let digits: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                    let code = u32::from_str_radix(&digits, 16).unwrap_or(0);
+                    value.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                Some(c) => value.push(c),
+                None => (),
+            }
+        }
+
+        value
+    }
+}
+
+#[derive(AsRefStr, Copy, Clone, PartialEq)]
+pub enum TriviaKind
+{
+    // Spaces, tabs and carriage returns
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
+}
+
+// Source code between tokens that doesn't affect the program
+pub struct Trivia<'a>
+{
+    pub kind: TriviaKind,
+    pub str: &'a [u8],
+}
+
+// A token together with the trivia around it.
+// Joining the trivia and the text of every token
+// gives back the exact source code.
+pub struct LosslessToken<'a>
+{
+    pub token: Token<'a>,
+    // Everything the token was scanned from. It is longer than
+    // the lexeme for errors that point inside of a token.
+    pub text: &'a [u8],
+    pub leading: Vec<Trivia<'a>>,
+    // Trivia on the same line as the token,
+    // except for block comments
+    pub trailing: Vec<Trivia<'a>>,
 }
 
 pub struct Scanner<'a>
@@ -75,23 +275,33 @@ pub struct Scanner<'a>
     start: u32,
     current: u32,
     line: u32,
+    // Column of `current`, kept up to date while advancing,
+    // so long lines don't have to be counted again for each token
+    column: u32,
+    // Line and column of the token being scanned
+    start_line: u32,
+    start_column: u32,
     source: &'a [u8],
+    // Skipped trivia is only kept in lossless mode
+    trivia: Option<Vec<Trivia<'a>>>,
+    // Set once the iterator has returned the end of file token
+    finished: bool,
 }
 
 macro_rules! two_char_token {
     ($enum:expr, $enum_equal:expr, $self:expr) => {
         if $self.match_next('=')
         {
-            Ok($self.make_token($enum_equal))
+            $self.make_token($enum_equal)
         }
         else
         {
-            Ok($self.make_token($enum))
+            $self.make_token($enum)
         }
     };
 }
 
-impl Scanner<'_>
+impl<'a> Scanner<'a>
 {
     pub fn new(source: &str) -> Scanner
     {
@@ -99,59 +309,148 @@ impl Scanner<'_>
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
             source: source.as_bytes(),
+            trivia: None,
+            finished: false,
+        }
+    }
+
+    // Starts scanning at `location` instead of at the beginning.
+    // Its line and column are where `offset` is in the whole source,
+    // for scanning a part of the source at a time.
+    pub fn starting_at(source: &str, location: Span) -> Scanner<'_>
+    {
+        Scanner {
+            start: location.offset,
+            current: location.offset,
+            line: location.line,
+            column: location.column,
+            ..Scanner::new(source)
+        }
+    }
+
+    // A scanner that keeps whitespace and comments,
+    // for tools that need to reproduce the source code
+    pub fn lossless(source: &str) -> Scanner<'_>
+    {
+        Scanner {
+            trivia: Some(Vec::new()),
+            ..Scanner::new(source)
         }
     }
 
-    pub fn scan_token(&mut self) -> Result<Token, err::Error>
+    // Only returns trivia when the scanner is lossless
+    pub fn scan_lossless(&mut self) -> LosslessToken<'a>
     {
-        self.skip_whitespace();
+        let token = self.scan_token();
+        let text = self.get_slice(self.start, self.current);
+        let leading = self.take_trivia();
 
-        self.start = self.current;
+        if token.kind != TokenKind::Eof
+        {
+            // Trailing trivia can't contain block comments,
+            // so it never fails
+            let _ = self.skip_whitespace(true);
+        }
+        let trailing = self.take_trivia();
+
+        LosslessToken {
+            token,
+            text,
+            leading,
+            trailing,
+        }
+    }
+
+    // Where the first character that hasn't been scanned yet is
+    pub fn location(&self) -> Span
+    {
+        Span {
+            offset: self.current,
+            line: self.line,
+            column: self.column,
+            length: 0,
+        }
+    }
+
+    fn take_trivia(&mut self) -> Vec<Trivia<'a>>
+    {
+        match &mut self.trivia
+        {
+            Some(trivia) => std::mem::take(trivia),
+            None => Vec::new(),
+        }
+    }
+
+    // Errors don't stop the scanning, they are returned
+    // as error tokens and the scanner moves on
+    pub fn scan_token(&mut self) -> Token<'a>
+    {
+        if let Err(kind) = self.skip_whitespace(false)
+        {
+            return self.make_token(kind);
+        }
+
+        self.begin_token();
 
         if self.is_end()
         {
-            return Ok(self.make_token(TokenKind::Eof));
+            return self.make_token(TokenKind::Eof);
         }
 
         let c = self.advance();
         return match c
         {
-            '(' => Ok(self.make_token(TokenKind::LeftParen)),
-            ')' => Ok(self.make_token(TokenKind::RightParen)),
-            '{' => Ok(self.make_token(TokenKind::LeftBrace)),
-            '}' => Ok(self.make_token(TokenKind::RightBrace)),
-            ';' => Ok(self.make_token(TokenKind::Semicolon)),
-            ',' => Ok(self.make_token(TokenKind::Comma)),
-            '.' => Ok(self.make_token(TokenKind::Dot)),
-            '-' => Ok(self.make_token(TokenKind::Minus)),
-            '+' => Ok(self.make_token(TokenKind::Plus)),
-            '/' => Ok(self.make_token(TokenKind::Slash)),
-            '*' => Ok(self.make_token(TokenKind::Star)),
+            '(' => self.make_token(TokenKind::LeftParen),
+            ')' => self.make_token(TokenKind::RightParen),
+            '{' => self.make_token(TokenKind::LeftBrace),
+            '}' => self.make_token(TokenKind::RightBrace),
+            ';' => self.make_token(TokenKind::Semicolon),
+            ',' => self.make_token(TokenKind::Comma),
+            '.' => self.make_token(TokenKind::Dot),
+            '-' => self.make_token(TokenKind::Minus),
+            '+' => self.make_token(TokenKind::Plus),
+            '/' =>
+            {
+                // Ordinary comments are skipped before
+                // getting here, so this is a doc comment
+                if self.peek() == '/'
+                {
+                    self.doc_comment()
+                }
+                else
+                {
+                    self.make_token(TokenKind::Slash)
+                }
+            }
+            '*' => self.make_token(TokenKind::Star),
             '!' =>
             {
-                two_char_token!(TokenKind::BangEqual, TokenKind::Bang, self)
+                two_char_token!(TokenKind::Bang, TokenKind::BangEqual, self)
             }
             '=' =>
             {
-                two_char_token!(TokenKind::EqualEqual, TokenKind::Equal, self)
+                two_char_token!(TokenKind::Equal, TokenKind::EqualEqual, self)
             }
             '<' =>
             {
-                two_char_token!(TokenKind::LessEqual, TokenKind::Less, self)
+                two_char_token!(TokenKind::Less, TokenKind::LessEqual, self)
             }
             '>' =>
             {
-                two_char_token!(TokenKind::GreaterEqual, TokenKind::Greater, self)
+                two_char_token!(TokenKind::Greater, TokenKind::GreaterEqual, self)
             }
             '"' => self.string(),
-            '0'..'9' => Ok(self.number()),
-            'a'..'z' | 'A'..'Z' | '_' => Ok(self.identifier()),
-            _ => Err(err::Error::CompileError("Unexpected character".to_string())),
+            '0'..='9' => self.number(),
+            c if is_identifier_start(c) => self.identifier(),
+            _ => self.make_token(TokenKind::Error(err::ErrorCode::UnexpectedCharacter)),
         };
     }
 
-    pub fn get_slice(&self, start: u32, end: u32) -> &[u8]
+    pub fn get_slice(&self, start: u32, end: u32) -> &'a [u8]
     {
         &self.source[start as usize..end as usize]
     }
@@ -161,61 +460,246 @@ impl Scanner<'_>
         self.get_char(self.current) as char == '\0'
     }
 
-    fn make_token(&self, kind: TokenKind) -> Token
+    fn make_token(&self, kind: TokenKind) -> Token<'a>
     {
         Token {
             kind,
             str: self.get_slice(self.start, self.current),
-            line: self.line,
+            span: self.span(),
         }
     }
 
-    fn string(&mut self) -> Result<Token, err::Error>
+    // Span of the token being scanned
+    fn span(&self) -> Span
+    {
+        Span {
+            offset: self.start,
+            line: self.start_line,
+            column: self.start_column,
+            length: self.current - self.start,
+        }
+    }
+
+    fn begin_token(&mut self)
+    {
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column;
+    }
+
+    // Called before advancing over the '\n',
+    // which moves the column to the first one
+    fn new_line(&mut self)
+    {
+        self.line += 1;
+        self.column = 0;
+    }
+
+    fn string(&mut self) -> Token<'a>
     {
-        while self.peek() != '"' && self.is_end()
+        // Only the first invalid escape is reported
+        let mut invalid_escape = None;
+
+        while self.peek() != '"' && !self.is_end()
         {
-            if self.peek() == '\n'
+            match self.peek()
             {
-                self.line += 1;
+                '\n' =>
+                {
+                    self.new_line();
+                    self.advance();
+                }
+                '\\' =>
+                {
+                    let span = Span {
+                        offset: self.current,
+                        line: self.line,
+                        column: self.column,
+                        length: 0,
+                    };
+
+                    if !self.escape() && invalid_escape.is_none()
+                    {
+                        invalid_escape = Some(Span {
+                            length: self.current - span.offset,
+                            ..span
+                        });
+                    }
+                }
+                _ =>
+                {
+                    self.advance();
+                }
             }
-            self.advance();
         }
 
         if self.is_end()
         {
-            return Err(err::Error::CompileError("Unterminated string.".to_string()));
+            return self.make_token(TokenKind::Error(err::ErrorCode::UnterminatedString));
         }
 
         // Skip closing quote
         self.advance();
 
-        Ok(self.make_token(TokenKind::String))
+        // The error points at the escape sequence
+        // instead of the whole string
+        if let Some(span) = invalid_escape
+        {
+            return Token {
+                kind: TokenKind::Error(err::ErrorCode::InvalidEscape),
+                str: self.get_slice(span.offset, span.offset + span.length),
+                span,
+            };
+        }
+
+        self.make_token(TokenKind::String)
+    }
+
+    // Consumes an escape sequence and
+    // returns false if it isn't valid
+    fn escape(&mut self) -> bool
+    {
+        // Skip the backslash
+        self.advance();
+
+        match self.peek()
+        {
+            'n' | 't' | '\\' | '"' =>
+            {
+                self.advance();
+                true
+            }
+            'u' =>
+            {
+                self.advance();
+                self.unicode_escape()
+            }
+            // Newlines and the end of the source are left
+            // for the string loop to handle
+            '\n' | '\0' => false,
+            _ =>
+            {
+                self.advance();
+                false
+            }
+        }
     }
 
-    fn number(&mut self) -> Token
+    // Scans the `{...}` part of `\u{...}`, which
+    // holds 1 to 6 hexadecimal digits of a code point
+    fn unicode_escape(&mut self) -> bool
     {
-        while self.peek().is_digit(10)
+        if !self.match_next('{')
+        {
+            return false;
+        }
+
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit()
         {
             self.advance();
         }
+        let digits =
+            unsafe { str::from_utf8_unchecked(self.get_slice(digits_start, self.current)) };
+
+        if !self.match_next('}') || digits.is_empty() || digits.len() > 6
+        {
+            return false;
+        }
+
+        // Surrogates and values above 0x10FFFF aren't characters
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .is_some()
+    }
 
-        if self.peek() == '.' && self.peek_next().is_digit(10)
+    fn number(&mut self) -> Token<'a>
+    {
+        // Scan again from the first digit
+        self.current = self.start;
+        self.column = self.start_column;
+
+        let mut valid;
+        if self.peek() == '0' && (self.peek_next() == 'x' || self.peek_next() == 'b')
         {
-            // Consume the "."
+            // Consume the "0x" or "0b"
             self.advance();
+            let radix = if self.advance() == 'x' { 16 } else { 2 };
+
+            valid = self.digits(radix);
+        }
+        else
+        {
+            valid = self.digits(10);
 
-            while self.peek().is_digit(10)
+            if self.peek() == '.' && self.peek_next().is_ascii_digit()
             {
+                // Consume the "."
                 self.advance();
+
+                valid &= self.digits(10);
+            }
+
+            if self.peek() == 'e' || self.peek() == 'E'
+            {
+                self.advance();
+
+                if self.peek() == '+' || self.peek() == '-'
+                {
+                    self.advance();
+                }
+
+                valid &= self.digits(10);
             }
         }
 
-        self.make_token(TokenKind::Number)
+        // Letters right after a number, like in `12abc`
+        // or `0b102`, are a part of the same mistake
+        if is_identifier_continue(self.peek())
+        {
+            valid = false;
+            while is_identifier_continue(self.peek())
+            {
+                self.advance();
+            }
+        }
+
+        if !valid
+        {
+            self.make_token(TokenKind::Error(err::ErrorCode::MalformedNumber))
+        }
+        else if parse_number(self.make_token(TokenKind::Number).as_str()).is_none()
+        {
+            self.make_token(TokenKind::Error(err::ErrorCode::NumberTooLarge))
+        }
+        else
+        {
+            self.make_token(TokenKind::Number)
+        }
+    }
+
+    // Consumes digits that may be separated by underscores.
+    // Returns false if there are no digits or
+    // an underscore isn't followed by a digit.
+    fn digits(&mut self, radix: u32) -> bool
+    {
+        let mut valid = self.peek().is_digit(radix);
+
+        while self.peek().is_digit(radix) || self.peek() == '_'
+        {
+            if self.advance() == '_' && !self.peek().is_digit(radix)
+            {
+                valid = false;
+            }
+        }
+
+        valid
     }
 
-    fn identifier(&mut self) -> Token
+    fn identifier(&mut self) -> Token<'a>
     {
-        while self.peek().is_alphabetic() || self.peek().is_digit(10) || self.peek() == '_'
+        while is_identifier_continue(self.peek())
         {
             self.advance();
         }
@@ -282,7 +766,7 @@ impl Scanner<'_>
         // 1. The lexeme is as long as the keyword
         // 2. All the characters match
         if self.current - self.start == start + length
-            && self.get_slice(self.start + start, self.start + length) == rest.as_bytes()
+            && self.get_slice(self.start + start, self.start + start + length) == rest.as_bytes()
         {
             kind
         }
@@ -292,19 +776,27 @@ impl Scanner<'_>
         }
     }
 
+    // The source comes from a &str, so it is valid UTF-8
+    // and `i` is always at the start of a character
     fn get_char(&self, i: u32) -> char
     {
-        match self.source.get(i as usize)
+        match self.source.get(i as usize..)
         {
-            Some(value) => *value as char,
+            Some(rest) => unsafe { str::from_utf8_unchecked(rest) }
+                .chars()
+                .next()
+                .unwrap_or('\0'),
             None => '\0',
         }
     }
 
+    // Columns count characters, not bytes
     fn advance(&mut self) -> char
     {
-        self.current += 1;
-        self.get_char(self.current - 1)
+        let c = self.get_char(self.current);
+        self.current += c.len_utf8() as u32;
+        self.column += 1;
+        c
     }
 
     fn match_next(&mut self, expected: char) -> bool
@@ -320,6 +812,7 @@ impl Scanner<'_>
         }
 
         self.current += 1;
+        self.column += 1;
 
         true
     }
@@ -331,41 +824,334 @@ impl Scanner<'_>
 
     fn peek_next(&self) -> char
     {
-        self.get_char(self.current + 1)
+        self.get_char(self.current + self.peek().len_utf8() as u32)
     }
 
-    fn skip_whitespace(&mut self)
+    // Returns the kind of the error token if a comment isn't terminated.
+    // With `trailing` it stops at the end of the line and at block comments.
+    fn skip_whitespace(&mut self, trailing: bool) -> Result<(), TokenKind>
     {
         loop
         {
-            let c = self.peek();
-            match c
+            let start = self.current;
+
+            let kind = match self.peek()
             {
                 ' ' | '\r' | '\t' =>
                 {
-                    self.advance();
+                    while self.peek() == ' ' || self.peek() == '\r' || self.peek() == '\t'
+                    {
+                        self.advance();
+                    }
+
+                    TriviaKind::Whitespace
                 }
-                '\n' =>
+                '\n' if !trailing =>
                 {
-                    self.line += 1;
+                    self.new_line();
                     self.advance();
+
+                    TriviaKind::Newline
                 }
-                '/' =>
+                '/' => match self.peek_next()
                 {
-                    if self.peek_next() == '/'
+                    '/' =>
                     {
+                        // `///` starts a doc comment, which becomes a token.
+                        // `////` and longer are ordinary comments.
+                        if self.get_char(self.current + 2) == '/'
+                            && self.get_char(self.current + 3) != '/'
+                        {
+                            return Ok(());
+                        }
+
                         while self.peek() != '\n' && !self.is_end()
                         {
                             self.advance();
                         }
+
+                        TriviaKind::LineComment
                     }
-                    else
+                    '*' if !trailing =>
                     {
-                        return;
+                        self.block_comment()?;
+
+                        TriviaKind::BlockComment
                     }
-                }
-                _ => return,
+                    _ => return Ok(()),
+                },
+                _ => return Ok(()),
             };
+
+            let str = self.get_slice(start, self.current);
+            if let Some(trivia) = &mut self.trivia
+            {
+                trivia.push(Trivia { kind, str });
+            }
+        }
+    }
+
+    // Block comments can be nested, so
+    // every `/*` needs its own `*/`
+    fn block_comment(&mut self) -> Result<(), TokenKind>
+    {
+        // The comment is the token if it's unterminated
+        self.begin_token();
+
+        // Skip the "/*"
+        self.advance();
+        self.advance();
+
+        let mut depth = 1;
+        while depth > 0
+        {
+            if self.is_end()
+            {
+                return Err(TokenKind::Error(err::ErrorCode::UnterminatedComment));
+            }
+
+            if self.peek() == '/' && self.peek_next() == '*'
+            {
+                self.advance();
+                depth += 1;
+            }
+            else if self.peek() == '*' && self.peek_next() == '/'
+            {
+                self.advance();
+                depth -= 1;
+            }
+            else if self.peek() == '\n'
+            {
+                self.new_line();
+            }
+
+            self.advance();
+        }
+
+        Ok(())
+    }
+
+    fn doc_comment(&mut self) -> Token<'a>
+    {
+        while self.peek() != '\n' && !self.is_end()
+        {
+            self.advance();
         }
+
+        self.make_token(TokenKind::DocComment)
+    }
+}
+
+// Yields every token up to and including the end of file token.
+// Error tokens are turned into errors.
+impl<'a> Iterator for Scanner<'a>
+{
+    type Item = Result<Token<'a>, err::Error>;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        if self.finished
+        {
+            return None;
+        }
+
+        let token = self.scan_token();
+        if token.kind == TokenKind::Eof
+        {
+            self.finished = true;
+        }
+
+        match token.to_error()
+        {
+            Some(error) => Some(Err(error)),
+            None => Some(Ok(token)),
+        }
+    }
+}
+
+// Identifiers follow Unicode Standard Annex #31,
+// with the underscore allowed as a first character
+fn is_identifier_start(c: char) -> bool
+{
+    c == '_' || UnicodeXID::is_xid_start(c)
+}
+
+pub fn is_identifier_continue(c: char) -> bool
+{
+    UnicodeXID::is_xid_continue(c)
+}
+
+// Converts the text of a number token into its value.
+// Returns None if the number doesn't fit into an f64.
+pub fn parse_number(text: &str) -> Option<f64>
+{
+    let text: String = text.chars().filter(|c| *c != '_').collect();
+
+    let value = if let Some(digits) = text.strip_prefix("0x")
+    {
+        parse_radix(digits, 16)?
+    }
+    else if let Some(digits) = text.strip_prefix("0b")
+    {
+        parse_radix(digits, 2)?
+    }
+    else
+    {
+        text.parse::<f64>().ok()?
+    };
+
+    if value.is_finite()
+    {
+        Some(value)
+    }
+    else
+    {
+        None
+    }
+}
+
+// Hex and binary integers of any length are converted with
+// a single rounding, which gives the closest f64 to the literal.
+// The first 64 bits are kept, and the bits after them only
+// matter for rounding, so they are folded into the last bit.
+fn parse_radix(digits: &str, radix: u32) -> Option<f64>
+{
+    let bits_per_digit = radix.trailing_zeros();
+    let mut mantissa: u64 = 0;
+    let mut bits = 0;
+    let mut exponent: u32 = 0;
+    let mut rest_nonzero = false;
+
+    for c in digits.trim_start_matches('0').chars()
+    {
+        let digit = c.to_digit(radix)? as u64;
+        if bits + bits_per_digit <= u64::BITS
+        {
+            mantissa = mantissa << bits_per_digit | digit;
+            bits += bits_per_digit;
+        }
+        else
+        {
+            exponent = exponent.saturating_add(bits_per_digit);
+            rest_nonzero |= digit != 0;
+        }
+    }
+
+    // Larger exponents are infinite anyway
+    let exponent = exponent.min(2 * f64::MAX_EXP as u32) as i32;
+    let mantissa = mantissa | rest_nonzero as u64;
+    Some(mantissa as f64 * 2f64.powi(exponent))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    // The kinds of the tokens before the end of file
+    fn kinds(source: &str) -> Vec<TokenKind>
+    {
+        Scanner::new(source)
+            .map(|token| match token
+            {
+                Ok(token) => token.kind,
+                Err(error) => TokenKind::Error(error.code),
+            })
+            .filter(|kind| *kind != TokenKind::Eof)
+            .collect()
+    }
+
+    fn number(source: &str) -> f64
+    {
+        let token = Scanner::new(source).scan_token();
+        assert_eq!(token.kind, TokenKind::Number, "in {:?}", source);
+        token.number_value()
+    }
+
+    fn assert_malformed(source: &str)
+    {
+        assert_eq!(
+            kinds(source),
+            vec![TokenKind::Error(err::ErrorCode::MalformedNumber)],
+            "in {:?}",
+            source
+        );
+    }
+
+    #[test]
+    fn number_literals()
+    {
+        assert_eq!(number("42"), 42.0);
+        assert_eq!(number("1.5"), 1.5);
+        assert_eq!(number("1e-9"), 1e-9);
+        assert_eq!(number("2.5E+3"), 2500.0);
+        assert_eq!(number("1_000_000"), 1_000_000.0);
+        assert_eq!(number("0x1F"), 31.0);
+        assert_eq!(number("0xff_ff"), 65535.0);
+        assert_eq!(number("0b1010"), 10.0);
+        assert_eq!(number("0b0000_0001"), 1.0);
+    }
+
+    #[test]
+    fn malformed_number_literals()
+    {
+        assert_malformed("0x");
+        assert_malformed("0b");
+        assert_malformed("1e");
+        assert_malformed("1e+");
+        assert_malformed("1_");
+        assert_malformed("1__0");
+        assert_malformed("0b102");
+        assert_malformed("0xfg");
+        assert_malformed("12abc");
+    }
+
+    #[test]
+    fn dot_without_digits_isnt_part_of_the_number()
+    {
+        assert_eq!(
+            kinds("1.foo"),
+            vec![TokenKind::Number, TokenKind::Dot, TokenKind::Identifier]
+        );
+    }
+
+    #[test]
+    fn large_integer_literals()
+    {
+        // 2^128 doesn't fit into a u128 but is an f64
+        let hex = format!("0x1{}", "0".repeat(32));
+        assert_eq!(number(&hex), 2f64.powi(128));
+
+        let binary = format!("0b1{}", "0".repeat(200));
+        assert_eq!(number(&binary), 2f64.powi(200));
+
+        let max = format!("0xfffffffffffff8{}", "0".repeat(242));
+        assert_eq!(number(&max), f64::MAX);
+        // 2^1024 - 1 rounds up to infinity
+        assert_eq!(
+            kinds(&format!("0x{}", "f".repeat(256))),
+            vec![TokenKind::Error(err::ErrorCode::NumberTooLarge)]
+        );
+        assert_eq!(
+            kinds(&format!("0x1{}", "0".repeat(256))),
+            vec![TokenKind::Error(err::ErrorCode::NumberTooLarge)]
+        );
+        assert_eq!(
+            kinds("1e400"),
+            vec![TokenKind::Error(err::ErrorCode::NumberTooLarge)]
+        );
+    }
+
+    #[test]
+    fn large_integer_literals_are_rounded_to_nearest()
+    {
+        // 2^53 + 1 is halfway between two f64 and rounds to even
+        assert_eq!(number("0x20000000000001"), 2f64.powi(53));
+        // Any bit after the halfway point rounds up
+        let above = format!("0x20000000000001{}1", "0".repeat(20));
+        assert_eq!(number(&above), (2f64.powi(53) + 2.0) * 2f64.powi(84));
+        // Halfway with an odd mantissa rounds up to even
+        let odd = format!("0x20000000000003{}", "0".repeat(20));
+        assert_eq!(number(&odd), (2f64.powi(53) + 4.0) * 2f64.powi(80));
     }
 }
@@ -0,0 +1,113 @@
+use super::compiler;
+use super::error::err;
+use super::interpreter::Interpreter;
+use super::natives;
+use super::parser;
+use super::register;
+use super::register_vm::RegisterVm;
+use super::resolver::{self, Lints};
+use super::value::Value;
+use super::vm::{Engine, Vm};
+
+// The engines are kept between runs, so code
+// can use the globals defined by earlier code
+pub struct Session
+{
+    pub engine: Engine,
+    pub lints: Lints,
+    vm: Vm,
+    register_vm: RegisterVm,
+    interpreter: Interpreter,
+}
+
+impl Session
+{
+    pub fn new(engine: Engine, lints: Lints) -> Self
+    {
+        Session {
+            engine,
+            lints,
+            vm: Vm::init(),
+            register_vm: RegisterVm::init(),
+            interpreter: Interpreter::init(),
+        }
+    }
+
+    pub fn interpreter(&self) -> &Interpreter
+    {
+        &self.interpreter
+    }
+
+    // Command-line arguments after the script, as the `args` global
+    pub fn set_args(&mut self, args: &[String])
+    {
+        self.interpreter.define_global("args", natives::args(args));
+    }
+
+    // Does what ROX_TRACE_EXECUTION does, for both virtual machines
+    pub fn set_trace(&mut self, on: bool)
+    {
+        self.vm.trace = on;
+        self.register_vm.trace = on;
+    }
+
+    // Prints the bytecode the engine would run under the heading `name`
+    pub fn disassemble(&self, source: &str, name: &str) -> Result<(), Vec<err::Error>>
+    {
+        let chunk = compiler::compile(source)?;
+
+        match self.engine
+        {
+            Engine::Register => register::generate(&chunk)
+                .map_err(|e| vec![e])?
+                .disassemble(name),
+            // The tree engine doesn't compile the code,
+            // so show the bytecode of the stack engine
+            Engine::Stack | Engine::Tree => chunk.disassemble(name),
+        }
+
+        Ok(())
+    }
+
+    // Compile errors are all reported at once,
+    // while a runtime error stops the execution
+    // and warnings are passed to `warn` before running.
+    // Returns the value of a trailing expression statement.
+    pub fn run(
+        &mut self,
+        source: &str,
+        warn: &dyn Fn(&err::Error),
+    ) -> Result<Option<Value>, Vec<err::Error>>
+    {
+        let statements = parser::parse(source)?;
+        let globals = self.interpreter.globals().keys();
+        let (warnings, errors): (Vec<_>, Vec<_>) =
+            resolver::resolve(&statements, &self.lints, globals)
+                .into_iter()
+                .partition(|e| e.is_warning());
+
+        warnings.iter().for_each(warn);
+        if !errors.is_empty()
+        {
+            return Err(errors);
+        }
+
+        let result = match self.engine
+        {
+            Engine::Stack =>
+            {
+                let chunk = compiler::generate(&statements)?;
+                self.vm.interpret(chunk)
+            }
+            Engine::Register =>
+            {
+                let chunk = compiler::generate(&statements)?;
+                let register_vm = &mut self.register_vm;
+                register::generate(&chunk).and_then(|chunk| register_vm.interpret(chunk))
+            }
+            Engine::Tree => self.interpreter.interpret(&statements),
+        };
+
+        result.map_err(|e| vec![e])
+    }
+}
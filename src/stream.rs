@@ -0,0 +1,343 @@
+use super::error::err;
+use super::scanner::{OwnedToken, Scanner, Span, TokenKind};
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::str;
+
+// Wraps an iterator so that any number of
+// items can be looked at before consuming them
+pub struct Lookahead<I: Iterator>
+{
+    iter: I,
+    buffer: VecDeque<I::Item>,
+}
+
+impl<I: Iterator> Lookahead<I>
+{
+    pub fn new(iter: I) -> Self
+    {
+        Lookahead {
+            iter,
+            buffer: VecDeque::new(),
+        }
+    }
+
+    // `peek(0)` is the item that `next` returns
+    pub fn peek(&mut self, n: usize) -> Option<&I::Item>
+    {
+        while self.buffer.len() <= n
+        {
+            let item = self.iter.next()?;
+            self.buffer.push_back(item);
+        }
+
+        self.buffer.get(n)
+    }
+}
+
+impl<I: Iterator> Iterator for Lookahead<I>
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        match self.buffer.pop_front()
+        {
+            Some(item) => Some(item),
+            None => self.iter.next(),
+        }
+    }
+}
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+// How far past the end of a token the scanner may look
+// to decide where the token ends: two characters of
+// at most four bytes each
+const MAX_LOOKAHEAD: usize = 8;
+
+// Scans source code as it is read, keeping only
+// the part that hasn't been scanned yet in memory
+pub struct ReaderScanner<R: Read>
+{
+    reader: R,
+    buffer: String,
+    // Bytes read after the last complete character
+    partial: Vec<u8>,
+    // Offset of the start of the buffer in the source
+    offset: u32,
+    // Where the next token starts, with its offset in the buffer
+    location: Span,
+    reader_done: bool,
+    finished: bool,
+}
+
+impl Scanner<'_>
+{
+    pub fn from_reader<R: Read>(reader: R) -> ReaderScanner<R>
+    {
+        ReaderScanner {
+            reader,
+            buffer: String::new(),
+            partial: Vec::new(),
+            offset: 0,
+            location: Span {
+                offset: 0,
+                line: 1,
+                column: 1,
+                length: 0,
+            },
+            reader_done: false,
+            finished: false,
+        }
+    }
+}
+
+impl<R: Read> ReaderScanner<R>
+{
+    // Returns error tokens like any other token and
+    // only fails when the source can't be read.
+    // Returns None after the end of file token.
+    pub fn next_token(&mut self) -> Result<Option<OwnedToken>, err::Error>
+    {
+        if self.finished
+        {
+            return Ok(None);
+        }
+
+        loop
+        {
+            let mut scanner = Scanner::starting_at(&self.buffer, self.location);
+            let token = scanner.scan_token();
+            let end = scanner.location();
+
+            // A token that reaches the end of the buffer
+            // might continue in the part that isn't read yet
+            if !self.reader_done && end.offset as usize + MAX_LOOKAHEAD > self.buffer.len()
+            {
+                if let Err(e) = self.fill_more()
+                {
+                    self.finished = true;
+                    return Err(e);
+                }
+                continue;
+            }
+
+            let mut token = token.into_owned();
+            token.span.offset += self.offset;
+
+            self.finished = token.kind == TokenKind::Eof;
+            self.location = end;
+            return Ok(Some(token));
+        }
+    }
+
+    // At least doubles what is left to scan, so a long
+    // token is scanned again only a few times
+    fn fill_more(&mut self) -> Result<(), err::Error>
+    {
+        let unscanned = self.buffer.len() - self.location.offset as usize;
+        let target = (2 * unscanned).max(CHUNK_SIZE);
+
+        self.fill()?;
+        while !self.reader_done && self.buffer.len() < target
+        {
+            self.fill()?;
+        }
+
+        Ok(())
+    }
+
+    fn fill(&mut self) -> Result<(), err::Error>
+    {
+        // Drop what was already scanned, it is only
+        // done here so the buffer isn't moved for every token
+        let scanned = self.location.offset as usize;
+        self.buffer.drain(..scanned);
+        self.offset += scanned as u32;
+        self.location.offset = 0;
+
+        let mut chunk = [0; CHUNK_SIZE];
+        let count = loop
+        {
+            match self.reader.read(&mut chunk)
+            {
+                Ok(count) => break count,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        if count == 0
+        {
+            self.reader_done = true;
+            if !self.partial.is_empty()
+            {
+                return Err(invalid_utf8());
+            }
+
+            return Ok(());
+        }
+
+        self.partial.extend_from_slice(&chunk[..count]);
+        let valid = match str::from_utf8(&self.partial)
+        {
+            Ok(text) => text.len(),
+            // The last character continues in the next chunk
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => return Err(invalid_utf8()),
+        };
+
+        // Only complete characters were checked above
+        self.buffer
+            .push_str(unsafe { str::from_utf8_unchecked(&self.partial[..valid]) });
+        self.partial.drain(..valid);
+
+        Ok(())
+    }
+}
+
+fn invalid_utf8() -> err::Error
+{
+    io::Error::new(io::ErrorKind::InvalidData, "source code is not valid UTF-8").into()
+}
+
+// Yields the same tokens and errors as iterating over a `Scanner`.
+// Reading stops at the first I/O error.
+impl<R: Read> Iterator for ReaderScanner<R>
+{
+    type Item = Result<OwnedToken, err::Error>;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        match self.next_token()
+        {
+            Ok(Some(token)) => match token.as_token().to_error()
+            {
+                Some(error) => Some(Err(error)),
+                None => Some(Ok(token)),
+            },
+            Ok(None) => None,
+            Err(error) => Some(Err(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    // Hands out one byte for every read, so every
+    // token and character is split across chunks
+    struct ByteReader<'a>
+    {
+        bytes: &'a [u8],
+    }
+
+    impl Read for ByteReader<'_>
+    {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+        {
+            match self.bytes.split_first()
+            {
+                Some((byte, rest)) if !buf.is_empty() =>
+                {
+                    buf[0] = *byte;
+                    self.bytes = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn assert_same_tokens(source: &str)
+    {
+        let mut expected = Scanner::new(source);
+        let mut actual = Scanner::from_reader(ByteReader {
+            bytes: source.as_bytes(),
+        });
+
+        loop
+        {
+            let token = expected.scan_token();
+            let owned = actual.next_token().unwrap().unwrap();
+            let streamed = owned.as_token();
+
+            assert_eq!(streamed.kind, token.kind, "in {:?}", source);
+            assert_eq!(streamed.str, token.str, "in {:?}", source);
+            assert_eq!(streamed.span, token.span, "in {:?}", source);
+
+            if token.kind == TokenKind::Eof
+            {
+                break;
+            }
+        }
+
+        assert!(actual.next_token().unwrap().is_none());
+    }
+
+    #[test]
+    fn same_tokens_as_scanner()
+    {
+        assert_same_tokens("");
+        assert_same_tokens("var x = 1.5 + 2;\nprint x >= 3 and !nil;");
+        assert_same_tokens("var café = \"naïve ☃ 😀\";\nprint café;");
+        assert_same_tokens("\"a string\nover two lines\" 😀");
+        assert_same_tokens("/* a /* nested */ comment ☃ */ print 1; // done");
+        assert_same_tokens("/// a doc comment\nfun f() {}");
+        assert_same_tokens("print 0x1F + 0b101 + 1_000 + 2.5e3;");
+    }
+
+    #[test]
+    fn same_errors_as_scanner()
+    {
+        assert_same_tokens("print @ # 1;");
+        assert_same_tokens("\"unterminated ☃");
+        assert_same_tokens("/* unterminated /* comment */");
+        assert_same_tokens("1e + 0x;");
+    }
+
+    // Scanning them again for every chunk that is
+    // read would take minutes instead of seconds
+    #[test]
+    fn long_tokens()
+    {
+        let comment = format!("print 1;\n/* {} */\nprint 2;", "☃ x".repeat(1_000_000));
+        let string = format!("print \"{}\";", "y".repeat(4_000_000));
+
+        for source in [comment, string].iter()
+        {
+            let expected: Vec<Span> = Scanner::new(source)
+                .map(|token| token.unwrap().span)
+                .collect();
+            let actual: Vec<Span> = Scanner::from_reader(source.as_bytes())
+                .map(|token| token.unwrap().span)
+                .collect();
+
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_is_an_error()
+    {
+        let mut scanner = Scanner::from_reader(ByteReader {
+            bytes: b"print \xff;",
+        });
+
+        assert!(scanner.any(|token| token.is_err()));
+    }
+
+    #[test]
+    fn lookahead_peeks_without_consuming()
+    {
+        let mut items = Lookahead::new(1..4);
+
+        assert_eq!(items.peek(1), Some(&2));
+        assert_eq!(items.peek(0), Some(&1));
+        assert_eq!(items.peek(3), None);
+        assert_eq!(items.collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,216 @@
+use super::diagnostic::{escape_json, span_to_json};
+use super::error::err;
+use super::scanner::{Scanner, Token, TokenKind};
+use std::convert::AsRef;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+// Output formats of `rox tokens`
+#[derive(Copy, Clone, PartialEq)]
+pub enum TokenFormat
+{
+    // One token per line, the line number is
+    // only shown for the first token on a line
+    Text,
+    // One JSON object per token
+    Json,
+    // A header row followed by one row per token
+    Csv,
+}
+
+impl FromStr for TokenFormat
+{
+    type Err = err::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err>
+    {
+        match name
+        {
+            "text" => Ok(TokenFormat::Text),
+            "json" => Ok(TokenFormat::Json),
+            "csv" => Ok(TokenFormat::Csv),
+            _ => Err(err::Error::new(
+                err::ErrorCode::InvalidArguments,
+                format!(
+                    "unknown token format '{}'. Expected 'text', 'json' or 'csv'.",
+                    name
+                ),
+            )),
+        }
+    }
+}
+
+// Writes every token of the source, including error tokens, and
+// returns the errors so they can be reported after the tokens.
+// The source is scanned as it is read, so it is never all in memory.
+pub fn dump<R: Read, W: Write>(
+    source: R,
+    format: TokenFormat,
+    out: &mut W,
+) -> Result<Vec<err::Error>, err::Error>
+{
+    let mut scanner = Scanner::from_reader(source);
+    let mut errors = Vec::new();
+    let mut current_line = 0;
+
+    if format == TokenFormat::Csv
+    {
+        write!(out, "kind,code,text,offset,line,column,length\n")?;
+    }
+
+    while let Some(token) = scanner.next_token()?
+    {
+        let token = token.as_token();
+
+        match format
+        {
+            TokenFormat::Text => write_text(&token, &mut current_line, out)?,
+            TokenFormat::Json => write!(out, "{}\n", to_json(&token))?,
+            TokenFormat::Csv => write!(out, "{}\n", to_csv(&token))?,
+        }
+
+        if let Some(error) = token.to_error()
+        {
+            errors.push(error);
+        }
+    }
+
+    Ok(errors)
+}
+
+fn write_text<W: Write>(
+    token: &Token,
+    current_line: &mut u32,
+    out: &mut W,
+) -> Result<(), err::Error>
+{
+    // If tokens are on the same line
+    // it displays the pipe character '|' instead
+    // of a line number
+    if token.span.line != *current_line
+    {
+        write!(out, "{}", token.span.line)?;
+        *current_line = token.span.line;
+    }
+    else
+    {
+        write!(out, "|")?;
+    }
+
+    // Where the token starts, and the bytes it covers
+    let span = token.span;
+    let location = format!("{}:{}", span.line, span.column);
+    let range = format!("{}..{}", span.offset, span.offset + span.length);
+    write!(
+        out,
+        "\t{:14} {:8} {:12} '{}'\n",
+        token.kind.as_ref(),
+        location,
+        range,
+        token.as_str()
+    )?;
+
+    Ok(())
+}
+
+// The error code of an error token
+fn code(token: &Token) -> Option<err::ErrorCode>
+{
+    match token.kind
+    {
+        TokenKind::Error(code) => Some(code),
+        _ => None,
+    }
+}
+
+fn to_json(token: &Token) -> String
+{
+    let code = match code(token)
+    {
+        Some(code) => format!("\"{}\"", code),
+        None => String::from("null"),
+    };
+
+    format!(
+        "{{\"kind\":{},\"code\":{},\"text\":{},\"span\":{}}}",
+        escape_json(token.kind.as_ref()),
+        code,
+        escape_json(token.as_str()),
+        span_to_json(token.span),
+    )
+}
+
+fn to_csv(token: &Token) -> String
+{
+    let code = match code(token)
+    {
+        Some(code) => code.to_string(),
+        None => String::new(),
+    };
+
+    format!(
+        "{},{},{},{},{},{},{}",
+        token.kind.as_ref(),
+        code,
+        escape_csv(token.as_str()),
+        token.span.offset,
+        token.span.line,
+        token.span.column,
+        token.span.length,
+    )
+}
+
+// Quotes fields that contain separators, quotes or
+// line breaks, doubling the quotes inside of them
+fn escape_csv(text: &str) -> String
+{
+    if !text.contains([',', '"', '\n', '\r'])
+    {
+        return String::from(text);
+    }
+
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn dump_to_string(source: &str, format: TokenFormat) -> String
+    {
+        let mut out = Vec::new();
+        dump(source.as_bytes(), format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn text_includes_spans()
+    {
+        assert_eq!(
+            dump_to_string("print 1;\n  x", TokenFormat::Text),
+            "1\tPrint          1:1      0..5         'print'\n\
+             |\tNumber         1:7      6..7         '1'\n\
+             |\tSemicolon      1:8      7..8         ';'\n\
+             2\tIdentifier     2:3      11..12       'x'\n\
+             |\tEof            2:4      12..12       ''\n"
+        );
+    }
+
+    #[test]
+    fn csv_quotes_fields()
+    {
+        let csv = dump_to_string("\"a,b\"", TokenFormat::Csv);
+        assert_eq!(csv.lines().nth(1), Some("String,,\"\"\"a,b\"\"\",0,1,1,5"));
+    }
+
+    #[test]
+    fn errors_are_returned()
+    {
+        let mut out = Vec::new();
+        let errors = dump("@ #".as_bytes(), TokenFormat::Json, &mut out).unwrap();
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 3);
+    }
+}
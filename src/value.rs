@@ -1,104 +1,132 @@
 use super::err;
 pub use std::fmt::{self, Formatter};
-use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::rc::Rc;
 
-#[derive(Copy, Clone)]
+#[derive(Clone, PartialEq)]
 pub enum Value
 {
     Double(f64),
+    Bool(bool),
+    Nil,
+    String(Rc<str>),
+    List(Rc<Vec<Value>>),
+    Native(Native),
+}
+
+// A function implemented in Rust that scripts can call
+#[derive(Copy, Clone)]
+pub struct Native
+{
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&[Value]) -> Result<Value, err::Error>,
+}
+
+// Natives are equal when they are the same function
+impl PartialEq for Native
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.name == other.name
+    }
 }
 
 impl fmt::Display for Value
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result
     {
-        let value = match self
+        match self
         {
-            Value::Double(x) => x,
+            Value::Double(x) => write!(f, "{}", x)?,
+            Value::Bool(b) => write!(f, "{}", b)?,
+            Value::Nil => write!(f, "nil")?,
+            Value::String(string) => write!(f, "{}", string)?,
+            Value::List(values) =>
+            {
+                let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                write!(f, "[{}]", values.join(", "))?
+            }
+            Value::Native(native) => write!(f, "<native fn {}>", native.name)?,
         };
 
-        write!(f, "{}", value)?;
-
         Ok(())
     }
 }
 
 macro_rules! arithmetic_op_impl {
-    ($op:ident, $err_msg:literal) => {
-        type Output = Self;
-
-        fn $op(self, rhs: Self) -> Self::Output
+    ($name:ident, $op:tt, $err_msg:literal) => {
+        // `self` is the left operand, so `a.try_sub(b)` is `a - b`
+        pub fn $name(self, rhs: Self) -> Result<Value, err::Error>
         {
-            if let Value::Double(rhs) = rhs
+            match (self, rhs)
             {
-                return match self
-                {
-                    Value::Double(value) => Value::Double(rhs.$op(value)),
-                };
+                (Value::Double(a), Value::Double(b)) => Ok(Value::Double(a $op b)),
+                _ => Err(err::Error::new(err::ErrorCode::TypeMismatch, $err_msg)),
             }
-
-            err::error(err::Error::RuntimeError($err_msg.to_string()));
-
-            Value::Double(0.0)
         }
     };
 }
 
-impl Add for Value
-{
-    type Output = Self;
-
-    fn add(self, rhs: Self) -> Self::Output
-    {
-        // TODO: Add strings concatenation here later
-        if let Value::Double(rhs) = rhs
+macro_rules! comparison_op_impl {
+    ($name:ident, $op:tt) => {
+        pub fn $name(self, rhs: Self) -> Result<Value, err::Error>
         {
-            return match self
+            match (self, rhs)
             {
-                Value::Double(value) => Value::Double(rhs + value),
-            };
+                (Value::Double(a), Value::Double(b)) => Ok(Value::Bool(a $op b)),
+                _ => Err(err::Error::new(
+                    err::ErrorCode::TypeMismatch,
+                    "only numbers can be compared.",
+                )),
+            }
         }
-
-        err::error(err::Error::RuntimeError(
-            "only numbers can be added together.".to_string(),
-        ));
-
-        Value::Double(0.0)
-    }
-}
-
-impl Sub for Value
-{
-    arithmetic_op_impl!(sub, "only numbers can be subtracted.");
-}
-
-impl Mul for Value
-{
-    arithmetic_op_impl!(mul, "only numbers can be multiplied together.");
-}
-
-impl Div for Value
-{
-    arithmetic_op_impl!(div, "only numbers can be divided together.");
+    };
 }
 
-impl Neg for Value
+// Arithmetic fails instead of producing a value
+// when the operands have the wrong types
+impl Value
 {
-    type Output = Self;
-
-    fn neg(self) -> Self::Output
+    // TODO: Add strings concatenation here later
+    arithmetic_op_impl!(try_add, +, "only numbers can be added together.");
+    arithmetic_op_impl!(try_sub, -, "only numbers can be subtracted.");
+    arithmetic_op_impl!(try_mul, *, "only numbers can be multiplied together.");
+    arithmetic_op_impl!(try_div, /, "only numbers can be divided together.");
+
+    comparison_op_impl!(try_less, <);
+    comparison_op_impl!(try_less_equal, <=);
+    comparison_op_impl!(try_greater, >);
+    comparison_op_impl!(try_greater_equal, >=);
+
+    pub fn try_neg(self) -> Result<Value, err::Error>
     {
         match self
         {
-            Value::Double(value) => Value::Double(-value),
-            _ =>
-            {
-                err::error(err::Error::RuntimeError(
-                    "only numbers can be negated.".to_string(),
-                ));
+            Value::Double(value) => Ok(Value::Double(-value)),
+            _ => Err(err::Error::new(
+                err::ErrorCode::TypeMismatch,
+                "only numbers can be negated.",
+            )),
+        }
+    }
 
-                Value::Double(0.0)
-            }
+    // Name of the type, as shown to the user
+    pub fn type_name(&self) -> &'static str
+    {
+        match self
+        {
+            Value::Double(_) => "number",
+            Value::Bool(_) => "bool",
+            Value::Nil => "nil",
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Native(_) => "native function",
         }
     }
+
+    // `nil` and `false` are the only falsey values
+    pub fn is_falsey(&self) -> bool
+    {
+        matches!(self, Value::Nil | Value::Bool(false))
+    }
 }
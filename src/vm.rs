@@ -1,91 +1,87 @@
 use super::chunk::{Chunk, Instruction};
 use super::error::*;
+use super::scanner::Span;
 use super::value::Value;
 use std::env;
 use std::io::{self, Write};
+use std::str::FromStr;
+
+// Execution engines that can run compiled code
+#[derive(Copy, Clone)]
+pub enum Engine
+{
+    Stack,
+    Register,
+    // Walks the syntax tree instead of compiling it
+    Tree,
+}
+
+impl FromStr for Engine
+{
+    type Err = err::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err>
+    {
+        match name
+        {
+            "stack" => Ok(Engine::Stack),
+            "register" => Ok(Engine::Register),
+            "tree" => Ok(Engine::Tree),
+            _ => Err(err::Error::new(
+                err::ErrorCode::UnknownEngine,
+                format!(
+                    "unknown engine '{}'. Expected 'stack', 'register' or 'tree'.",
+                    name
+                ),
+            )),
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct Vm
 {
     chunk: Chunk,
+    // Prints the stack before every instruction
+    pub trace: bool,
 }
 
 impl Vm
 {
     pub fn init() -> Self
     {
-        Default::default()
+        Vm {
+            trace: Vm::is_backtrace_enabled(),
+            ..Default::default()
+        }
     }
 
-    pub fn interpret(&mut self, chunk: Chunk) -> Result<(), err::Error>
+    // Returns the value left on the stack, if any
+    pub fn interpret(&mut self, chunk: Chunk) -> Result<Option<Value>, err::Error>
     {
         self.chunk = chunk;
-        self.run()
+        self.run()?;
+
+        Ok(self.chunk.stack.pop())
     }
 
     fn run(&mut self) -> Result<(), err::Error>
     {
-        let is_backtrace_on = Vm::is_backtrace_enabled();
-
         let code = self.chunk.code.clone();
         for instr in code.iter()
         {
-            if is_backtrace_on
+            if self.trace
             {
                 self.chunk.print_stack()?;
             }
 
-            match instr
+            if let Err(e) = self.execute(instr)
             {
-                Instruction::Constant {
-                    op: _,
-                    line: _,
-                    offset,
-                } =>
-                {
-                    let constant = self.read_constant(*offset as usize);
-                    self.chunk.stack.push(constant);
-
-                    write!(io::stdout(), "{}\n", constant)?;
-                }
-                Instruction::Add { .. } =>
-                {
-                    let b = self.pop_from_stack()?;
-                    let a = self.pop_from_stack()?;
-                    self.chunk.stack.push(a + b);
-                }
-                Instruction::Subtract { .. } =>
-                {
-                    let b = self.pop_from_stack()?;
-                    let a = self.pop_from_stack()?;
-                    self.chunk.stack.push(a - b);
-                }
-                Instruction::Multiply { .. } =>
-                {
-                    let b = self.pop_from_stack()?;
-                    let a = self.pop_from_stack()?;
-                    self.chunk.stack.push(a * b);
-                }
-                Instruction::Divide { .. } =>
-                {
-                    let b = self.pop_from_stack()?;
-                    let a = self.pop_from_stack()?;
-                    self.chunk.stack.push(a / b);
-                }
-                Instruction::Negate { .. } =>
-                {
-                    let value = self.pop_from_stack()?;
-                    self.chunk.stack.push(-value);
-                }
-                Instruction::Return { .. } => match self.chunk.stack.pop()
-                {
-                    Some(value) => write!(io::stdout(), "{}\n", value)?,
-                    None => write!(io::stdout(), "<empty>\n")?,
-                },
-            };
+                return Err(runtime_error(e, instr.span()));
+            }
         }
 
-        if is_backtrace_on
+        if self.trace
         {
             self.chunk.disassemble("backtrace_chunk");
         }
@@ -93,9 +89,70 @@ impl Vm
         Ok(())
     }
 
+    fn execute(&mut self, instr: &Instruction) -> Result<(), err::Error>
+    {
+        match instr
+        {
+            Instruction::Constant {
+                op: _,
+                span: _,
+                offset,
+            } =>
+            {
+                let constant = self.read_constant(*offset as usize);
+                self.chunk.stack.push(constant);
+            }
+            Instruction::Add { .. } =>
+            {
+                let b = self.pop_from_stack()?;
+                let a = self.pop_from_stack()?;
+                self.chunk.stack.push(a.try_add(b)?);
+            }
+            Instruction::Subtract { .. } =>
+            {
+                let b = self.pop_from_stack()?;
+                let a = self.pop_from_stack()?;
+                self.chunk.stack.push(a.try_sub(b)?);
+            }
+            Instruction::Multiply { .. } =>
+            {
+                let b = self.pop_from_stack()?;
+                let a = self.pop_from_stack()?;
+                self.chunk.stack.push(a.try_mul(b)?);
+            }
+            Instruction::Divide { .. } =>
+            {
+                let b = self.pop_from_stack()?;
+                let a = self.pop_from_stack()?;
+                self.chunk.stack.push(a.try_div(b)?);
+            }
+            Instruction::Negate { .. } =>
+            {
+                let value = self.pop_from_stack()?;
+                self.chunk.stack.push(value.try_neg()?);
+            }
+            Instruction::Print { .. } =>
+            {
+                let value = self.pop_from_stack()?;
+                write!(io::stdout(), "{}\n", value)?;
+            }
+            Instruction::Pop { .. } =>
+            {
+                self.pop_from_stack()?;
+            }
+            Instruction::Return { .. } => match self.chunk.stack.pop()
+            {
+                Some(value) => write!(io::stdout(), "{}\n", value)?,
+                None => write!(io::stdout(), "<empty>\n")?,
+            },
+        };
+
+        Ok(())
+    }
+
     fn read_constant(&self, index: usize) -> Value
     {
-        self.chunk.constants[index]
+        self.chunk.constants[index].clone()
     }
 
     fn pop_from_stack(&mut self) -> Result<Value, err::Error>
@@ -105,16 +162,18 @@ impl Vm
             Some(value) => value,
             None =>
             {
-                return Err(err::Error::RuntimeError(String::from(
+                return Err(err::Error::new(
+                    err::ErrorCode::StackUnderflow,
                     "failed to get a value from the stack. The stack is empty.",
-                )));
+                )
+                .with_note("this is a bug in the compiler"));
             }
         };
 
         Ok(value)
     }
 
-    fn is_backtrace_enabled() -> bool
+    pub fn is_backtrace_enabled() -> bool
     {
         if let Ok(_) = env::var("ROX_TRACE_EXECUTION")
         {
@@ -124,3 +183,21 @@ impl Vm
         false
     }
 }
+
+// Points the error at the instruction that failed
+// and records the call stack at the time of failure
+pub fn runtime_error(error: err::Error, span: Span) -> err::Error
+{
+    // The error might already point inside the statement
+    let span = error.span.unwrap_or(span);
+    let mut error = error.at(span);
+
+    // There are no functions yet, so the whole
+    // program runs in the top-level script frame
+    error.trace = vec![err::Frame {
+        function: String::from("script"),
+        line: span.line,
+    }];
+
+    error
+}